@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+use tinyrenderer_rs::{
+    draw_triangles, Camera, Color, Colorf, CullMode, Framebuffer, Mat4, Model, RasterizerState,
+    Shader, Texture2D, Texture2DFilterMode, Texture2DWrapMode, Vec2, Vec3, Vec4,
+};
+
+const WIDTH: i32 = 1024;
+const HEIGHT: i32 = 1024;
+const FRAMES: u32 = 20;
+
+struct TextureShader<'a> {
+    model: &'a Model,
+    texture: Option<&'a Texture2D>,
+    mvp: Mat4,
+}
+
+impl Shader for TextureShader<'_> {
+    type Varying = Vec2;
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        let i = face * 3 + vert;
+        (self.mvp * self.model.verts[i].push(1f32), self.model.uvs[i])
+    }
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf> {
+        match self.texture {
+            Some(texture) => Some(texture.texture(
+                varying.x,
+                varying.y,
+                Texture2DWrapMode::ClampToEdge,
+                Texture2DFilterMode::Nearest,
+            )),
+            None => Some(Colorf::new(varying.x, varying.y, 0f32, 1f32)),
+        }
+    }
+}
+
+fn bench(name: &str, model: &str, texture: &str, eye: Vec3, target: Vec3) {
+    let model = Model::load(model).unwrap();
+    let texture = Texture2D::load(texture).unwrap();
+    let mut camera = Camera::perspective(
+        std::f32::consts::FRAC_PI_4,
+        WIDTH as f32 / HEIGHT as f32,
+        0.1f32,
+        100f32,
+    );
+    camera.look_at(&eye, &target, &Vec3::y());
+    let mut framebuffer = Framebuffer::create(WIDTH, HEIGHT).unwrap();
+    framebuffer.set_rasterizer_state(&RasterizerState {
+        cull_mode: CullMode::Back,
+        ..Default::default()
+    });
+    for (shading, texture) in [("textured", Some(&texture)), ("flat", None)] {
+        let shader = TextureShader {
+            model: &model,
+            texture,
+            mvp: camera.view_projection_matrix(),
+        };
+        let mut elapsed = Duration::ZERO;
+        for _ in 0..FRAMES {
+            framebuffer.clear_color_with(&Color::black());
+            framebuffer.clear_depth();
+            let start = Instant::now();
+            draw_triangles(&mut framebuffer, &shader, model.verts.len() / 3);
+            elapsed += start.elapsed();
+        }
+        println!(
+            "{:<16} {:<10} {:>8.2} ms/frame",
+            name,
+            shading,
+            elapsed.as_secs_f64() * 1000f64 / FRAMES as f64
+        );
+    }
+}
+
+fn main() {
+    bench(
+        "african_head",
+        "assets/african_head/african_head.obj",
+        "assets/african_head/african_head_diffuse.png",
+        Vec3::new(1f32, 0.5f32, 3f32),
+        Vec3::zeros(),
+    );
+    bench(
+        "diablo3_pose",
+        "assets/diablo3_pose/diablo3_pose.obj",
+        "assets/diablo3_pose/diablo3_pose_diffuse.png",
+        Vec3::new(1f32, 0.5f32, 3f32),
+        Vec3::zeros(),
+    );
+    bench(
+        "floor_grazing",
+        "assets/floor/floor.obj",
+        "assets/floor/floor_diffuse.png",
+        Vec3::new(0.3f32, -0.95f32, 1.2f32),
+        Vec3::new(-0.3f32, -1f32, -1f32),
+    );
+}
@@ -0,0 +1,165 @@
+use crate::Colorf;
+
+// Maximum number of color attachments of a framebuffer, including its primary color buffer.
+pub const MAX_COLOR_ATTACHMENTS: usize = 8;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AttachmentFormat {
+    Rgba8,
+    Rgba32F,
+    R32F,
+    // Two unsigned normalized 16-bit channels.
+    Rg16,
+}
+
+impl AttachmentFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            AttachmentFormat::Rgba8 => 4,
+            AttachmentFormat::Rgba32F => 16,
+            AttachmentFormat::R32F => 4,
+            AttachmentFormat::Rg16 => 4,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            AttachmentFormat::Rgba8 | AttachmentFormat::Rgba32F => 4,
+            AttachmentFormat::R32F => 1,
+            AttachmentFormat::Rg16 => 2,
+        }
+    }
+
+    // Converts `color` to the format and stores it in the `bytes_per_pixel` bytes of `dst`.
+    // Missing channels are dropped and normalized formats are clamped to [0, 1].
+    pub(crate) fn encode(&self, color: &Colorf, dst: &mut [u8]) {
+        match self {
+            AttachmentFormat::Rgba8 => {
+                for (dst, value) in dst.iter_mut().zip(color.iter()) {
+                    *dst = (value.clamp(0f32, 1f32) * 255f32).round() as u8;
+                }
+            }
+            AttachmentFormat::Rgba32F | AttachmentFormat::R32F => {
+                for (dst, value) in dst.chunks_exact_mut(4).zip(color.iter()) {
+                    dst.copy_from_slice(&value.to_ne_bytes());
+                }
+            }
+            AttachmentFormat::Rg16 => {
+                for (dst, value) in dst.chunks_exact_mut(2).zip(color.iter()) {
+                    let value = (value.clamp(0f32, 1f32) * 65535f32).round() as u16;
+                    dst.copy_from_slice(&value.to_ne_bytes());
+                }
+            }
+        }
+    }
+
+    // Reads a pixel back, missing color channels are 0 and a missing alpha is 1.
+    pub(crate) fn decode(&self, src: &[u8]) -> Colorf {
+        let mut color = Colorf::new(0f32, 0f32, 0f32, 1f32);
+        match self {
+            AttachmentFormat::Rgba8 => {
+                for (value, src) in color.iter_mut().zip(src.iter()) {
+                    *value = *src as f32 / 255f32;
+                }
+            }
+            AttachmentFormat::Rgba32F | AttachmentFormat::R32F => {
+                for (value, src) in color.iter_mut().zip(src.chunks_exact(4)) {
+                    *value = f32::from_ne_bytes(src.try_into().unwrap());
+                }
+            }
+            AttachmentFormat::Rg16 => {
+                for (value, src) in color.iter_mut().zip(src.chunks_exact(2)) {
+                    *value = u16::from_ne_bytes(src.try_into().unwrap()) as f32 / 65535f32;
+                }
+            }
+        }
+        color
+    }
+}
+
+// An additional color attachment, stored with the same sample layout as the color buffer.
+pub(crate) struct Attachment {
+    pub(crate) format: AttachmentFormat,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Attachment {
+    pub(crate) fn new(format: AttachmentFormat, len: usize) -> Self {
+        Attachment {
+            format,
+            data: vec![0; len * format.bytes_per_pixel()],
+        }
+    }
+
+    pub(crate) fn get(&self, offset: usize) -> Colorf {
+        let size = self.format.bytes_per_pixel();
+        self.format
+            .decode(&self.data[offset * size..(offset + 1) * size])
+    }
+
+    pub(crate) fn set(&mut self, offset: usize, color: &Colorf) {
+        let size = self.format.bytes_per_pixel();
+        self.format
+            .encode(color, &mut self.data[offset * size..(offset + 1) * size]);
+    }
+
+    pub(crate) fn fill(&mut self, color: &Colorf) {
+        let size = self.format.bytes_per_pixel();
+        let mut pixel = vec![0; size];
+        self.format.encode(color, &mut pixel);
+        for dst in self.data.chunks_exact_mut(size) {
+            dst.copy_from_slice(&pixel);
+        }
+    }
+}
+
+// A mutable view of a range of the pixels of an attachment, see `FramebufferRows`.
+pub(crate) struct AttachmentRows<'a> {
+    pub(crate) format: AttachmentFormat,
+    pub(crate) data: &'a mut [u8],
+}
+
+impl AttachmentRows<'_> {
+    pub(crate) fn get(&self, offset: usize) -> Colorf {
+        let size = self.format.bytes_per_pixel();
+        self.format
+            .decode(&self.data[offset * size..(offset + 1) * size])
+    }
+
+    pub(crate) fn set(&mut self, offset: usize, color: &Colorf) {
+        let size = self.format.bytes_per_pixel();
+        self.format
+            .encode(color, &mut self.data[offset * size..(offset + 1) * size]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_attachment_format() {
+        let color = Colorf::new(0.25f32, -0.5f32, 1.5f32, 0.75f32);
+        let cases = [
+            (
+                AttachmentFormat::Rgba8,
+                Colorf::new(64f32 / 255f32, 0f32, 1f32, 191f32 / 255f32),
+            ),
+            (AttachmentFormat::Rgba32F, color),
+            (
+                AttachmentFormat::R32F,
+                Colorf::new(0.25f32, 0f32, 0f32, 1f32),
+            ),
+            (
+                AttachmentFormat::Rg16,
+                Colorf::new(16384f32 / 65535f32, 0f32, 0f32, 1f32),
+            ),
+        ];
+        for (format, expected) in cases {
+            let mut pixel = vec![0; format.bytes_per_pixel()];
+            format.encode(&color, &mut pixel);
+            assert_relative_eq!(format.decode(&pixel), expected);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{Colorf, Vec3};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+    ConstantColor,
+    OneMinusConstantColor,
+    ConstantAlpha,
+    OneMinusConstantAlpha,
+    SrcAlphaSaturate,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BlendEquation {
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BlendState {
+    pub enabled: bool,
+    pub src_color: BlendFactor,
+    pub dst_color: BlendFactor,
+    pub color_equation: BlendEquation,
+    pub src_alpha: BlendFactor,
+    pub dst_alpha: BlendFactor,
+    pub alpha_equation: BlendEquation,
+    pub constant: Colorf,
+}
+
+impl Default for BlendState {
+    fn default() -> Self {
+        Self::disabled()
+    }
+}
+
+impl BlendState {
+    pub fn new(src: BlendFactor, dst: BlendFactor, equation: BlendEquation) -> Self {
+        BlendState {
+            enabled: true,
+            src_color: src,
+            dst_color: dst,
+            color_equation: equation,
+            src_alpha: src,
+            dst_alpha: dst,
+            alpha_equation: equation,
+            constant: Colorf::zeros(),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        BlendState {
+            enabled: false,
+            ..Self::new(BlendFactor::One, BlendFactor::Zero, BlendEquation::Add)
+        }
+    }
+
+    // Straight (non-premultiplied) alpha over the destination.
+    pub fn alpha() -> Self {
+        BlendState {
+            src_alpha: BlendFactor::One,
+            ..Self::new(
+                BlendFactor::SrcAlpha,
+                BlendFactor::OneMinusSrcAlpha,
+                BlendEquation::Add,
+            )
+        }
+    }
+
+    pub fn premultiplied_alpha() -> Self {
+        Self::new(
+            BlendFactor::One,
+            BlendFactor::OneMinusSrcAlpha,
+            BlendEquation::Add,
+        )
+    }
+
+    pub fn additive() -> Self {
+        Self::new(BlendFactor::One, BlendFactor::One, BlendEquation::Add)
+    }
+
+    fn factor(&self, factor: BlendFactor, src: &Colorf, dst: &Colorf) -> Colorf {
+        let one = Colorf::repeat(1f32);
+        match factor {
+            BlendFactor::Zero => Colorf::zeros(),
+            BlendFactor::One => one,
+            BlendFactor::SrcColor => *src,
+            BlendFactor::OneMinusSrcColor => one - src,
+            BlendFactor::DstColor => *dst,
+            BlendFactor::OneMinusDstColor => one - dst,
+            BlendFactor::SrcAlpha => Colorf::repeat(src.w),
+            BlendFactor::OneMinusSrcAlpha => Colorf::repeat(1f32 - src.w),
+            BlendFactor::DstAlpha => Colorf::repeat(dst.w),
+            BlendFactor::OneMinusDstAlpha => Colorf::repeat(1f32 - dst.w),
+            BlendFactor::ConstantColor => self.constant,
+            BlendFactor::OneMinusConstantColor => one - self.constant,
+            BlendFactor::ConstantAlpha => Colorf::repeat(self.constant.w),
+            BlendFactor::OneMinusConstantAlpha => Colorf::repeat(1f32 - self.constant.w),
+            BlendFactor::SrcAlphaSaturate => {
+                let f = src.w.min(1f32 - dst.w);
+                Colorf::new(f, f, f, 1f32)
+            }
+        }
+    }
+
+    fn equation(
+        equation: BlendEquation,
+        src: f32,
+        src_factor: f32,
+        dst: f32,
+        dst_factor: f32,
+    ) -> f32 {
+        match equation {
+            BlendEquation::Add => src * src_factor + dst * dst_factor,
+            BlendEquation::Subtract => src * src_factor - dst * dst_factor,
+            BlendEquation::ReverseSubtract => dst * dst_factor - src * src_factor,
+            BlendEquation::Min => src.min(dst),
+            BlendEquation::Max => src.max(dst),
+        }
+    }
+
+    pub fn blend(&self, src: &Colorf, dst: &Colorf) -> Colorf {
+        if !self.enabled {
+            return *src;
+        }
+        let src_color = self.factor(self.src_color, src, dst);
+        let dst_color = self.factor(self.dst_color, src, dst);
+        let src_alpha = self.factor(self.src_alpha, src, dst);
+        let dst_alpha = self.factor(self.dst_alpha, src, dst);
+        let rgb = Vec3::from_fn(|i, _| {
+            Self::equation(
+                self.color_equation,
+                src[i],
+                src_color[i],
+                dst[i],
+                dst_color[i],
+            )
+        });
+        let a = Self::equation(self.alpha_equation, src.w, src_alpha.w, dst.w, dst_alpha.w);
+        rgb.push(a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_blend() {
+        let src = Colorf::new(1f32, 0f32, 0f32, 0.25f32);
+        let dst = Colorf::new(0f32, 0f32, 1f32, 1f32);
+        assert_relative_eq!(BlendState::disabled().blend(&src, &dst), src);
+        assert_relative_eq!(
+            BlendState::alpha().blend(&src, &dst),
+            Colorf::new(0.25f32, 0f32, 0.75f32, 1f32)
+        );
+        let premultiplied = Colorf::new(0.25f32, 0f32, 0f32, 0.25f32);
+        assert_relative_eq!(
+            BlendState::premultiplied_alpha().blend(&premultiplied, &dst),
+            Colorf::new(0.25f32, 0f32, 0.75f32, 1f32)
+        );
+        assert_relative_eq!(
+            BlendState::additive().blend(&src, &dst),
+            Colorf::new(1f32, 0f32, 1f32, 1.25f32)
+        );
+        let state = BlendState {
+            constant: Colorf::new(0.5f32, 0.5f32, 0.5f32, 0.5f32),
+            ..BlendState::new(
+                BlendFactor::ConstantColor,
+                BlendFactor::One,
+                BlendEquation::ReverseSubtract,
+            )
+        };
+        assert_relative_eq!(
+            state.blend(&src, &dst),
+            Colorf::new(-0.5f32, 0f32, 1f32, 0.875f32)
+        );
+        let state = BlendState::new(BlendFactor::One, BlendFactor::One, BlendEquation::Min);
+        assert_relative_eq!(
+            state.blend(&src, &dst),
+            Colorf::new(0f32, 0f32, 0f32, 0.25f32)
+        );
+    }
+}
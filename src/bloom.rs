@@ -0,0 +1,206 @@
+use crate::{Framebuffer, FramebufferError, PostProcessPass, Vec3};
+
+// A linear rgb image for the intermediate levels of the bloom.
+struct Image {
+    width: i32,
+    height: i32,
+    pixels: Vec<Vec3>,
+}
+
+impl Image {
+    fn new(width: i32, height: i32) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![Vec3::zeros(); (width * height) as usize],
+        }
+    }
+
+    // Reads a pixel, coordinates are clamped to the edges.
+    fn get(&self, x: i32, y: i32) -> Vec3 {
+        let x = x.clamp(0, self.width - 1);
+        let y = y.clamp(0, self.height - 1);
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    fn set(&mut self, x: i32, y: i32, color: &Vec3) {
+        self.pixels[(y * self.width + x) as usize] = *color;
+    }
+
+    // Halves the size, averaging blocks of 2x2 pixels.
+    fn downsample(&self) -> Image {
+        let mut image = Image::new((self.width / 2).max(1), (self.height / 2).max(1));
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let color = self.get(x * 2, y * 2)
+                    + self.get(x * 2 + 1, y * 2)
+                    + self.get(x * 2, y * 2 + 1)
+                    + self.get(x * 2 + 1, y * 2 + 1);
+                image.set(x, y, &(color / 4f32));
+            }
+        }
+        image
+    }
+
+    // Convolves with the kernel `weights` along x, then along y.
+    fn blur(&self, weights: &[f32]) -> Image {
+        let radius = (weights.len() / 2) as i32;
+        let mut horizontal = Image::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| self.get(x + i as i32 - radius, y) * *weight)
+                    .sum();
+                horizontal.set(x, y, &color);
+            }
+        }
+        let mut image = Image::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| horizontal.get(x, y + i as i32 - radius) * *weight)
+                    .sum();
+                image.set(x, y, &color);
+            }
+        }
+        image
+    }
+
+    // Bilinear sample at normalized coordinates, with pixel centers at (x + 0.5) / width.
+    fn sample(&self, u: f32, v: f32) -> Vec3 {
+        let x = u * self.width as f32 - 0.5f32;
+        let y = v * self.height as f32 - 0.5f32;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let xt = x - x0;
+        let yt = y - y0;
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        self.get(x0, y0) * (1f32 - xt) * (1f32 - yt)
+            + self.get(x0 + 1, y0) * xt * (1f32 - yt)
+            + self.get(x0, y0 + 1) * (1f32 - xt) * yt
+            + self.get(x0 + 1, y0 + 1) * xt * yt
+    }
+}
+
+// Normalized weights of a gaussian kernel covering 3 standard deviations on each side.
+fn gaussian_weights(sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3f32).ceil().max(1f32) as i32;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|i| (-((i * i) as f32) / (2f32 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = weights.iter().sum();
+    weights.iter().map(|weight| weight / sum).collect()
+}
+
+// Spreads the light of pixels brighter than `threshold` to their surroundings. The bright pixels
+// are blurred at `levels` successively halved resolutions starting from half of the framebuffer,
+// then the levels are averaged, scaled by `intensity` and added to the source colors.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Bloom {
+    // Compared with the largest channel of the linear color.
+    pub threshold: f32,
+    pub intensity: f32,
+    pub levels: usize,
+    // Standard deviation of the blur of every level, in pixels of that level.
+    pub sigma: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Bloom {
+            threshold: 1f32,
+            intensity: 0.5f32,
+            levels: 4,
+            sigma: 2f32,
+        }
+    }
+}
+
+impl Bloom {
+    pub fn new(threshold: f32, intensity: f32) -> Self {
+        Bloom {
+            threshold,
+            intensity,
+            ..Self::default()
+        }
+    }
+}
+
+impl PostProcessPass for Bloom {
+    fn apply(
+        &self,
+        source: &Framebuffer,
+        target: &mut Framebuffer,
+    ) -> Result<(), FramebufferError> {
+        let (width, height) = (source.width, source.height);
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let mut bright = Image::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let color = source.get_attachment(0, x, y)?.xyz();
+                let brightness = color.max();
+                if brightness > self.threshold {
+                    bright.set(x, y, &(color * (brightness - self.threshold) / brightness));
+                }
+            }
+        }
+        let weights = gaussian_weights(self.sigma);
+        let mut level = bright.downsample();
+        let mut levels = Vec::with_capacity(self.levels);
+        for _ in 0..self.levels {
+            let blurred = level.blur(&weights);
+            level = blurred.downsample();
+            levels.push(blurred);
+        }
+        let scale = self.intensity / self.levels.max(1) as f32;
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f32 + 0.5f32) / width as f32;
+                let v = (y as f32 + 0.5f32) / height as f32;
+                let glow: Vec3 = levels.iter().map(|level| level.sample(u, v)).sum();
+                let color = source.get_attachment(0, x, y)?;
+                let color = (color.xyz() + glow * scale).push(color.w);
+                target.set_attachment(0, x, y, &color)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_image() {
+        let weights = gaussian_weights(1.5f32);
+        assert_eq!(weights.len(), 11);
+        assert_relative_eq!(weights.iter().sum::<f32>(), 1f32);
+        assert_relative_eq!(weights[4], weights[6]);
+
+        let mut image = Image::new(32, 32);
+        image.set(12, 16, &Vec3::repeat(16f32));
+        let blurred = image.blur(&weights);
+        let total: Vec3 = blurred.pixels.iter().sum();
+        assert_relative_eq!(total, Vec3::repeat(16f32), epsilon = 1e-3);
+        let half = image.downsample();
+        assert_eq!((half.width, half.height), (16, 16));
+        assert_relative_eq!(half.get(6, 8), Vec3::repeat(4f32));
+        assert_relative_eq!(half.sample(0.40625f32, 0.53125f32), Vec3::repeat(4f32));
+        assert_relative_eq!(half.sample(0.4375f32, 0.53125f32), Vec3::repeat(2f32));
+    }
+
+    #[test]
+    fn test_empty_framebuffer() {
+        let source = Framebuffer::create_hdr(0, 0).unwrap();
+        let mut target = Framebuffer::create_hdr(0, 0).unwrap();
+        assert!(Bloom::new(1f32, 1f32).apply(&source, &mut target).is_ok());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{Mat4, Vec3};
+
+// Projections follow the renderer's depth convention where a larger depth is closer to the
+// viewer, so the near plane maps to NDC z = 1 and the far plane to NDC z = -1.
+#[derive(Copy, Clone, Debug)]
+pub enum Projection {
+    Perspective {
+        fov_y: f32,
+        aspect: f32,
+        near: f32,
+        far: f32,
+    },
+    Orthographic {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+impl Projection {
+    pub fn matrix(&self) -> Mat4 {
+        match *self {
+            Projection::Perspective {
+                fov_y,
+                aspect,
+                near,
+                far,
+            } => {
+                let f = 1f32 / (fov_y / 2f32).tan();
+                Mat4::new(
+                    f / aspect,
+                    0f32,
+                    0f32,
+                    0f32,
+                    0f32,
+                    f,
+                    0f32,
+                    0f32,
+                    0f32,
+                    0f32,
+                    (far + near) / (far - near),
+                    2f32 * far * near / (far - near),
+                    0f32,
+                    0f32,
+                    -1f32,
+                    0f32,
+                )
+            }
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                near,
+                far,
+            } => Mat4::new(
+                2f32 / (right - left),
+                0f32,
+                0f32,
+                -(right + left) / (right - left),
+                0f32,
+                2f32 / (top - bottom),
+                0f32,
+                -(top + bottom) / (top - bottom),
+                0f32,
+                0f32,
+                2f32 / (far - near),
+                (far + near) / (far - near),
+                0f32,
+                0f32,
+                0f32,
+                1f32,
+            ),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub projection: Projection,
+}
+
+impl Camera {
+    pub fn new(eye: Vec3, target: Vec3, up: Vec3, projection: Projection) -> Self {
+        Camera {
+            eye,
+            target,
+            up,
+            projection,
+        }
+    }
+
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Self::new(
+            Vec3::new(0f32, 0f32, 1f32),
+            Vec3::zeros(),
+            Vec3::y(),
+            Projection::Perspective {
+                fov_y,
+                aspect,
+                near,
+                far,
+            },
+        )
+    }
+
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        Self::new(
+            Vec3::new(0f32, 0f32, 1f32),
+            Vec3::zeros(),
+            Vec3::y(),
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                near,
+                far,
+            },
+        )
+    }
+
+    pub fn look_at(&mut self, eye: &Vec3, target: &Vec3, up: &Vec3) {
+        self.eye = *eye;
+        self.target = *target;
+        self.up = *up;
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        (self.target - self.eye).normalize()
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        let z = (self.eye - self.target).normalize();
+        let x = self.up.cross(&z).normalize();
+        let y = z.cross(&x);
+        Mat4::new(
+            x.x,
+            x.y,
+            x.z,
+            -x.dot(&self.eye),
+            y.x,
+            y.y,
+            y.z,
+            -y.dot(&self.eye),
+            z.x,
+            z.y,
+            z.z,
+            -z.dot(&self.eye),
+            0f32,
+            0f32,
+            0f32,
+            1f32,
+        )
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        self.projection.matrix()
+    }
+
+    pub fn view_projection_matrix(&self) -> Mat4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vec4;
+    use approx::assert_relative_eq;
+
+    fn project(m: &Mat4, p: &Vec3) -> Vec3 {
+        let p = m * p.push(1f32);
+        p.xyz() / p.w
+    }
+
+    #[test]
+    fn test_view_matrix() {
+        let mut camera = Camera::perspective(1f32, 1f32, 0.1f32, 10f32);
+        camera.look_at(
+            &Vec3::new(3f32, 0f32, 0f32),
+            &Vec3::zeros(),
+            &Vec3::new(0f32, 1f32, 0f32),
+        );
+        let view = camera.view_matrix();
+        assert_relative_eq!(
+            view * Vec4::new(3f32, 0f32, 0f32, 1f32),
+            Vec4::new(0f32, 0f32, 0f32, 1f32)
+        );
+        assert_relative_eq!(
+            view * Vec4::new(0f32, 0f32, 0f32, 1f32),
+            Vec4::new(0f32, 0f32, -3f32, 1f32),
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(camera.forward(), Vec3::new(-1f32, 0f32, 0f32));
+    }
+
+    #[test]
+    fn test_projection_depth() {
+        let perspective = Camera::perspective(1f32, 1f32, 1f32, 10f32).projection_matrix();
+        assert_relative_eq!(project(&perspective, &Vec3::new(0f32, 0f32, -1f32)).z, 1f32);
+        assert_relative_eq!(
+            project(&perspective, &Vec3::new(0f32, 0f32, -10f32)).z,
+            -1f32
+        );
+        let orthographic =
+            Camera::orthographic(-2f32, 2f32, -1f32, 1f32, 1f32, 10f32).projection_matrix();
+        assert_relative_eq!(
+            project(&orthographic, &Vec3::new(2f32, 1f32, -1f32)),
+            Vec3::new(1f32, 1f32, 1f32)
+        );
+        assert_relative_eq!(
+            project(&orthographic, &Vec3::new(0f32, 0f32, -10f32)).z,
+            -1f32
+        );
+    }
+}
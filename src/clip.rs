@@ -0,0 +1,146 @@
+use crate::{Varying, Vec4};
+
+const CLIP_PLANES: [Vec4; 6] = [
+    Vec4::new(1f32, 0f32, 0f32, 1f32),
+    Vec4::new(-1f32, 0f32, 0f32, 1f32),
+    Vec4::new(0f32, 1f32, 0f32, 1f32),
+    Vec4::new(0f32, -1f32, 0f32, 1f32),
+    Vec4::new(0f32, 0f32, 1f32, 1f32),
+    Vec4::new(0f32, 0f32, -1f32, 1f32),
+];
+
+fn outcode(p: &Vec4) -> u8 {
+    let mut code = 0;
+    for (i, plane) in CLIP_PLANES.iter().enumerate() {
+        if plane.dot(p) < 0f32 {
+            code |= 1 << i;
+        }
+    }
+    code
+}
+
+// Sutherland–Hodgman clipping of a triangle against the six planes of the clip-space frustum
+// -w <= x, y, z <= w. Returns the clipped convex polygon, which is empty when the triangle is
+// entirely outside.
+pub fn clip_triangle<V: Varying>(clip_coords: &[Vec4; 3], varyings: &[V; 3]) -> Vec<(Vec4, V)> {
+    let codes = [
+        outcode(&clip_coords[0]),
+        outcode(&clip_coords[1]),
+        outcode(&clip_coords[2]),
+    ];
+    let mut polygon: Vec<(Vec4, V)> = (0..3).map(|i| (clip_coords[i], varyings[i])).collect();
+    if codes[0] | codes[1] | codes[2] == 0 {
+        return polygon;
+    }
+    if codes[0] & codes[1] & codes[2] != 0 {
+        polygon.clear();
+        return polygon;
+    }
+    let mut output: Vec<(Vec4, V)> = Vec::with_capacity(polygon.len() + CLIP_PLANES.len());
+    for (i, plane) in CLIP_PLANES.iter().enumerate() {
+        if (codes[0] | codes[1] | codes[2]) & (1 << i) == 0 {
+            continue;
+        }
+        output.clear();
+        for j in 0..polygon.len() {
+            let (p0, v0) = &polygon[j];
+            let (p1, v1) = &polygon[(j + 1) % polygon.len()];
+            let d0 = plane.dot(p0);
+            let d1 = plane.dot(p1);
+            if d0 >= 0f32 {
+                output.push((*p0, *v0));
+            }
+            if (d0 >= 0f32) != (d1 >= 0f32) {
+                let t = d0 / (d0 - d1);
+                output.push((p0.lerp(p1, t), V::lerp(v0, v1, t)));
+            }
+        }
+        std::mem::swap(&mut polygon, &mut output);
+        if polygon.len() < 3 {
+            polygon.clear();
+            break;
+        }
+    }
+    polygon
+}
+
+pub fn clip_line(p0: &Vec4, p1: &Vec4) -> Option<(Vec4, Vec4)> {
+    let code0 = outcode(p0);
+    let code1 = outcode(p1);
+    if code0 | code1 == 0 {
+        return Some((*p0, *p1));
+    }
+    if code0 & code1 != 0 {
+        return None;
+    }
+    let mut t0 = 0f32;
+    let mut t1 = 1f32;
+    for plane in CLIP_PLANES.iter() {
+        let d0 = plane.dot(p0);
+        let d1 = plane.dot(p1);
+        if d0 < 0f32 && d1 < 0f32 {
+            return None;
+        }
+        if d0 < 0f32 {
+            t0 = t0.max(d0 / (d0 - d1));
+        } else if d1 < 0f32 {
+            t1 = t1.min(d0 / (d0 - d1));
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some((p0.lerp(p1, t0), p0.lerp(p1, t1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_clip_triangle() {
+        let inside = [
+            Vec4::new(-0.5f32, -0.5f32, 0f32, 1f32),
+            Vec4::new(0.5f32, -0.5f32, 0f32, 1f32),
+            Vec4::new(0f32, 0.5f32, 0f32, 1f32),
+        ];
+        assert_eq!(clip_triangle(&inside, &[0f32, 1f32, 2f32]).len(), 3);
+
+        let outside = [
+            Vec4::new(2f32, 2f32, 0f32, 1f32),
+            Vec4::new(3f32, 2f32, 0f32, 1f32),
+            Vec4::new(2f32, 3f32, 0f32, 1f32),
+        ];
+        assert!(clip_triangle(&outside, &[0f32, 1f32, 2f32]).is_empty());
+
+        // One corner sticks out through the near plane, leaving a quad behind.
+        let crossing = [
+            Vec4::new(0f32, 0f32, 0f32, 1f32),
+            Vec4::new(0.5f32, 0f32, 0f32, 1f32),
+            Vec4::new(0f32, 0f32, 3f32, 1f32),
+        ];
+        let polygon = clip_triangle(&crossing, &[0f32, 1f32, 3f32]);
+        assert_eq!(polygon.len(), 4);
+        for (p, v) in polygon.iter() {
+            assert!(p.z <= p.w + f32::EPSILON);
+            assert_relative_eq!(*v, p.x * 2f32 + p.z, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_clip_line() {
+        let (p0, p1) = clip_line(
+            &Vec4::new(-2f32, 0f32, 0f32, 1f32),
+            &Vec4::new(2f32, 0f32, 0f32, 1f32),
+        )
+        .unwrap();
+        assert_relative_eq!(p0, Vec4::new(-1f32, 0f32, 0f32, 1f32));
+        assert_relative_eq!(p1, Vec4::new(1f32, 0f32, 0f32, 1f32));
+        assert!(clip_line(
+            &Vec4::new(-2f32, 2f32, 0f32, 1f32),
+            &Vec4::new(2f32, 2f32, 0f32, 1f32)
+        )
+        .is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::Vec4;
+use std::sync::OnceLock;
 
 #[derive(Copy, Clone, PartialEq, Debug, Default)]
 pub struct Color {
@@ -61,3 +62,99 @@ impl From<Colorf> for Color {
         )
     }
 }
+
+// Encoding of the rgb channels of 8-bit colors, alpha is always linear.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum ColorSpace {
+    #[default]
+    Linear,
+    Srgb,
+}
+
+// The piecewise sRGB transfer functions, for values in [0, 1].
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045f32 {
+        value / 12.92f32
+    } else {
+        ((value + 0.055f32) / 1.055f32).powf(2.4f32)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308f32 {
+        value * 12.92f32
+    } else {
+        1.055f32 * value.powf(1f32 / 2.4f32) - 0.055f32
+    }
+}
+
+// Linear value of every 8-bit sRGB code.
+fn srgb_decode_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| std::array::from_fn(|i| srgb_to_linear(i as f32 / 255f32)))
+}
+
+// Linear value halfway between consecutive sRGB codes, code i + 1 starts at entry i.
+fn srgb_encode_table() -> &'static [f32; 255] {
+    static TABLE: OnceLock<[f32; 255]> = OnceLock::new();
+    TABLE.get_or_init(|| std::array::from_fn(|i| srgb_to_linear((i as f32 + 0.5f32) / 255f32)))
+}
+
+impl ColorSpace {
+    pub fn decode(&self, color: &Color) -> Colorf {
+        match self {
+            ColorSpace::Linear => (*color).into(),
+            ColorSpace::Srgb => {
+                let table = srgb_decode_table();
+                Colorf::new(
+                    table[color.r as usize],
+                    table[color.g as usize],
+                    table[color.b as usize],
+                    color.a as f32 / 255f32,
+                )
+            }
+        }
+    }
+
+    // Rounds to the nearest code in the color space, values are clamped to [0, 1].
+    pub fn encode(&self, color: &Colorf) -> Color {
+        match self {
+            ColorSpace::Linear => (*color).into(),
+            ColorSpace::Srgb => {
+                let table = srgb_encode_table();
+                let encode = |value: f32| table.partition_point(|&edge| edge <= value) as u8;
+                Color::new(
+                    encode(color.x),
+                    encode(color.y),
+                    encode(color.z),
+                    (color.w.clamp(0f32, 1f32) * 255f32).round() as u8,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_srgb() {
+        assert_relative_eq!(srgb_to_linear(0f32), 0f32);
+        assert_relative_eq!(srgb_to_linear(1f32), 1f32);
+        assert_relative_eq!(srgb_to_linear(0.5f32), 0.21404f32, epsilon = 1e-5);
+        assert_relative_eq!(linear_to_srgb(0.5f32), 0.73536f32, epsilon = 1e-5);
+        assert_relative_eq!(linear_to_srgb(0.001f32), 0.01292f32);
+        for code in 0..=255u8 {
+            let color = Color::new(code, code, code, code);
+            let linear = ColorSpace::Srgb.decode(&color);
+            assert_relative_eq!(linear.x, srgb_to_linear(code as f32 / 255f32));
+            assert_eq!(ColorSpace::Srgb.encode(&linear), color);
+            let expected = (linear_to_srgb(linear.x) * 255f32).round() as u8;
+            assert_eq!(ColorSpace::Srgb.encode(&linear).r, expected);
+        }
+        let color = ColorSpace::Srgb.encode(&Colorf::new(-1f32, 0.5f32, 2f32, 0.5f32));
+        assert_eq!(color, Color::new(0, 188, 255, 128));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::material::{blinn_phong, specular_exponent};
+use crate::{
+    AttachmentFormat, Camera, ColorSpace, Colorf, Framebuffer, FramebufferError, Light, Mat4,
+    Model, Shader, Texture2D, Texture2DFilterMode, Texture2DWrapMode, Vec2, Vec3, Vec4,
+};
+
+// Color attachments of a `GBuffer`, positions are reconstructed from the depth buffer.
+pub const GBUFFER_ALBEDO: usize = 0;
+pub const GBUFFER_NORMAL: usize = 1;
+pub const GBUFFER_SPECULAR: usize = 2;
+
+// Specular exponents are stored in a normalized channel, divided by this value.
+const MAX_SPECULAR_EXPONENT: f32 = 512f32;
+
+pub struct GBuffer {
+    framebuffer: Framebuffer,
+}
+
+impl GBuffer {
+    pub fn create(width: i32, height: i32) -> Result<Self, FramebufferError> {
+        let mut framebuffer = Framebuffer::create(width, height)?;
+        // The albedo is stored sRGB-encoded to keep dark linear colors apart in 8 bits.
+        framebuffer.set_color_space(ColorSpace::Srgb);
+        framebuffer.add_attachment(AttachmentFormat::Rgba32F)?;
+        framebuffer.add_attachment(AttachmentFormat::Rg16)?;
+        Ok(GBuffer { framebuffer })
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    pub fn clear(&mut self) {
+        for index in 0..self.framebuffer.attachment_count() {
+            self.framebuffer
+                .clear_attachment_with(index, &Colorf::zeros())
+                .unwrap();
+        }
+        self.framebuffer.clear_depth();
+    }
+}
+
+// Geometry pass of the deferred renderer, writes the surface attributes of `model` to a `GBuffer`.
+// The normal attachment stores w = 1 for covered pixels.
+pub struct GBufferShader<'a> {
+    pub model: &'a Model,
+    pub diffuse: &'a Texture2D,
+    pub specular: Option<&'a Texture2D>,
+    pub specular_intensity: f32,
+    pub mvp: Mat4,
+}
+
+impl Shader for GBufferShader<'_> {
+    type Varying = (Vec2, Vec3);
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        let i = face * 3 + vert;
+        (
+            self.mvp * self.model.verts[i].push(1f32),
+            (self.model.uvs[i], self.model.norms[i]),
+        )
+    }
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf> {
+        let (uv, _) = varying;
+        Some(self.diffuse.texture(
+            uv.x,
+            uv.y,
+            Texture2DWrapMode::ClampToEdge,
+            Texture2DFilterMode::Linear,
+        ))
+    }
+
+    fn fragment_targets(&self, varying: &Self::Varying, targets: &mut [Option<Colorf>]) -> bool {
+        let (uv, norm) = varying;
+        targets[GBUFFER_ALBEDO] = self.fragment(varying);
+        targets[GBUFFER_NORMAL] = Some(norm.normalize().push(1f32));
+        targets[GBUFFER_SPECULAR] = Some(match self.specular {
+            Some(specular) => {
+                let sample = specular.texture(
+                    uv.x,
+                    uv.y,
+                    Texture2DWrapMode::ClampToEdge,
+                    Texture2DFilterMode::Linear,
+                );
+                let exponent = specular_exponent(&sample);
+                Colorf::new(
+                    self.specular_intensity,
+                    exponent / MAX_SPECULAR_EXPONENT,
+                    0f32,
+                    0f32,
+                )
+            }
+            None => Colorf::zeros(),
+        });
+        true
+    }
+}
+
+// Lighting pass of the deferred renderer. Shades every covered pixel of `gbuffer` with the diffuse
+// and specular terms of `Material` summed over `lights`, without its ambient and emissive terms,
+// and writes the linear color to `target`, uncovered pixels are left unchanged.
+pub fn draw_deferred_lighting(
+    target: &mut Framebuffer,
+    gbuffer: &GBuffer,
+    camera: &Camera,
+    lights: &[Light],
+) -> Result<(), FramebufferError> {
+    let source = &gbuffer.framebuffer;
+    if target.width != source.width || target.height != source.height {
+        return Err(FramebufferError::BadSize);
+    }
+    let viewport = source.viewport();
+    let inv_view_projection = camera
+        .view_projection_matrix()
+        .try_inverse()
+        .filter(|m| m.iter().all(|v| v.is_finite()))
+        .ok_or(FramebufferError::BadCamera)?;
+    for y in 0..source.height {
+        for x in 0..source.width {
+            let normal = source.get_attachment(GBUFFER_NORMAL, x, y)?;
+            if normal.w == 0f32 {
+                continue;
+            }
+            let normal = normal.xyz();
+            let albedo = source.get_attachment(GBUFFER_ALBEDO, x, y)?;
+            let specular = source.get_attachment(GBUFFER_SPECULAR, x, y)?;
+            let window = Vec3::new(x as f32 + 0.5f32, y as f32 + 0.5f32, source.get_depth(x, y));
+            let world = inv_view_projection * viewport.to_ndc(&window).push(1f32);
+            let position = world.xyz() / world.w;
+            let view_dir = (camera.eye - position).normalize();
+
+            let specular =
+                (specular.x > 0f32).then(|| (specular.x, specular.y * MAX_SPECULAR_EXPONENT));
+            let mut irradiance = Vec3::zeros();
+            for light in lights {
+                let (light_dir, radiance) = light.incident(&position);
+                irradiance += radiance * blinn_phong(&normal, &view_dir, &light_dir, specular);
+            }
+            let color = albedo.xyz().component_mul(&irradiance);
+            target.set_attachment(0, x, y, &color.push(albedo.w))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CompareFunction {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    Greater,
+    GreaterEqual,
+    NotEqual,
+    Always,
+}
+
+impl CompareFunction {
+    pub fn test<T: PartialOrd>(&self, value: T, reference: T) -> bool {
+        match self {
+            CompareFunction::Never => false,
+            CompareFunction::Less => value < reference,
+            CompareFunction::LessEqual => value <= reference,
+            CompareFunction::Equal => value == reference,
+            CompareFunction::Greater => value > reference,
+            CompareFunction::GreaterEqual => value >= reference,
+            CompareFunction::NotEqual => value != reference,
+            CompareFunction::Always => true,
+        }
+    }
+}
+
+// Larger depth values are closer to the viewer by default, see `Projection`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DepthState {
+    pub test_enabled: bool,
+    pub write_enabled: bool,
+    pub compare: CompareFunction,
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        DepthState {
+            test_enabled: true,
+            write_enabled: true,
+            compare: CompareFunction::Greater,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_function() {
+        let cases = [
+            (CompareFunction::Never, [false, false, false]),
+            (CompareFunction::Less, [true, false, false]),
+            (CompareFunction::LessEqual, [true, true, false]),
+            (CompareFunction::Equal, [false, true, false]),
+            (CompareFunction::Greater, [false, false, true]),
+            (CompareFunction::GreaterEqual, [false, true, true]),
+            (CompareFunction::NotEqual, [true, false, true]),
+            (CompareFunction::Always, [true, true, true]),
+        ];
+        for (compare, expected) in cases {
+            assert_eq!(compare.test(0f32, 0.5f32), expected[0]);
+            assert_eq!(compare.test(0.5f32, 0.5f32), expected[1]);
+            assert_eq!(compare.test(1f32, 0.5f32), expected[2]);
+        }
+    }
+}
@@ -1,12 +1,90 @@
 use std::path::Path;
 use std::slice;
 
-use crate::{image_write, Color, ImageWriteError};
+use crate::attachment::{Attachment, AttachmentRows, MAX_COLOR_ATTACHMENTS};
+use crate::{
+    image_write, AttachmentFormat, BlendState, Color, ColorSpace, Colorf, DepthState,
+    ImageWriteError, RasterizerState, RasterizerStats, Scissor, StencilState, ToneMapping, Vec2i,
+    Viewport,
+};
+
+// Standard multisample patterns, in 1/16 pixel units relative to the pixel centre.
+const SAMPLE_PATTERN_1: [(i32, i32); 1] = [(0, 0)];
+const SAMPLE_PATTERN_2: [(i32, i32); 2] = [(4, 4), (-4, -4)];
+const SAMPLE_PATTERN_4: [(i32, i32); 4] = [(-2, -6), (6, -2), (-6, 2), (2, 6)];
+const SAMPLE_PATTERN_8: [(i32, i32); 8] = [
+    (1, -3),
+    (-1, 3),
+    (5, 1),
+    (-3, -5),
+    (-5, 5),
+    (-7, -1),
+    (3, 7),
+    (7, -7),
+];
+
+// Storage of attachment 0. RGBA8 colors are encoded in the color space of the framebuffer,
+// RGBA32F colors are linear and not clamped. Depth-only framebuffers have no color storage.
+enum ColorBuffer {
+    Rgba8(Vec<Color>),
+    Rgba32F(Attachment),
+    None,
+}
+
+impl ColorBuffer {
+    fn rows_mut(&mut self) -> ColorBufferRows<'_> {
+        match self {
+            ColorBuffer::Rgba8(buffer) => ColorBufferRows::Rgba8(buffer),
+            ColorBuffer::Rgba32F(buffer) => ColorBufferRows::Rgba32F(AttachmentRows {
+                format: buffer.format,
+                data: &mut buffer.data,
+            }),
+            ColorBuffer::None => ColorBufferRows::None,
+        }
+    }
+
+    fn chunks_mut(&mut self, chunk_size: usize, chunk_count: usize) -> Vec<ColorBufferRows<'_>> {
+        match self {
+            ColorBuffer::Rgba8(buffer) => buffer
+                .chunks_mut(chunk_size)
+                .map(ColorBufferRows::Rgba8)
+                .collect(),
+            ColorBuffer::Rgba32F(buffer) => {
+                let format = buffer.format;
+                buffer
+                    .data
+                    .chunks_mut(chunk_size * format.bytes_per_pixel())
+                    .map(|data| ColorBufferRows::Rgba32F(AttachmentRows { format, data }))
+                    .collect()
+            }
+            ColorBuffer::None => (0..chunk_count).map(|_| ColorBufferRows::None).collect(),
+        }
+    }
+}
+
+enum ColorBufferRows<'a> {
+    Rgba8(&'a mut [Color]),
+    Rgba32F(AttachmentRows<'a>),
+    None,
+}
 
 pub struct Framebuffer {
-    color_buffer: Vec<Color>,
+    color_buffer: ColorBuffer,
+    // Color attachments 1.., attachment 0 is `color_buffer`.
+    attachments: Vec<Attachment>,
     depth_buffer: Vec<f32>,
-    depth_test: bool,
+    stencil_buffer: Vec<u8>,
+    samples: i32,
+    color_write: bool,
+    // Encoding of the color buffer, blending and resolving happen on linear values.
+    color_space: ColorSpace,
+    depth_state: DepthState,
+    stencil_state: StencilState,
+    blend_state: BlendState,
+    viewport: Viewport,
+    scissor: Option<Scissor>,
+    rasterizer_state: RasterizerState,
+    pub(crate) stats: RasterizerStats,
     pub width: i32,
     pub height: i32,
 }
@@ -15,6 +93,10 @@ pub struct Framebuffer {
 pub enum FramebufferError {
     BadSize,
     BadPosition,
+    BadSampleCount,
+    BadAttachment,
+    // The camera transform of a pass can't be inverted, e.g. eye == target.
+    BadCamera,
     ImageWriteError(ImageWriteError),
 }
 
@@ -33,28 +115,226 @@ impl Framebuffer {
         width: i32,
         height: i32,
         color: &Color,
+    ) -> Result<Self, FramebufferError> {
+        Self::create_multisample_init_color(width, height, 1, color)
+    }
+
+    pub fn create_multisample(
+        width: i32,
+        height: i32,
+        samples: i32,
+    ) -> Result<Self, FramebufferError> {
+        Self::create_multisample_init_color(width, height, samples, &Color::transparent())
+    }
+
+    pub fn create_multisample_init_color(
+        width: i32,
+        height: i32,
+        samples: i32,
+        color: &Color,
+    ) -> Result<Self, FramebufferError> {
+        Self::create_with_color_buffer(width, height, samples, |len| {
+            ColorBuffer::Rgba8(vec![*color; len])
+        })
+    }
+
+    // `color_buffer` creates the storage of attachment 0 for the given number of samples.
+    fn create_with_color_buffer(
+        width: i32,
+        height: i32,
+        samples: i32,
+        color_buffer: impl FnOnce(usize) -> ColorBuffer,
     ) -> Result<Self, FramebufferError> {
         if width < 0 || height < 0 {
             return Err(FramebufferError::BadSize);
         }
+        if ![1, 2, 4, 8].contains(&samples) {
+            return Err(FramebufferError::BadSampleCount);
+        }
+        let len = (width * height * samples) as usize;
         Ok(Framebuffer {
-            color_buffer: vec![*color; (width * height) as usize],
-            depth_buffer: vec![f32::MIN; (width * height) as usize],
-            depth_test: true,
+            color_buffer: color_buffer(len),
+            attachments: Vec::new(),
+            depth_buffer: vec![f32::MIN; len],
+            stencil_buffer: vec![0; len],
+            samples,
+            color_write: true,
+            color_space: ColorSpace::Linear,
+            depth_state: DepthState::default(),
+            stencil_state: StencilState::default(),
+            blend_state: BlendState::default(),
+            viewport: Viewport::new(0, 0, width, height),
+            scissor: None,
+            rasterizer_state: RasterizerState::default(),
+            stats: RasterizerStats::default(),
             width,
             height,
         })
     }
 
+    // A framebuffer with an RGBA32F color buffer, colors are not clamped until they are tone mapped
+    // to an 8-bit framebuffer with `tone_map_to`.
+    pub fn create_hdr(width: i32, height: i32) -> Result<Self, FramebufferError> {
+        Self::create_multisample_hdr(width, height, 1)
+    }
+
+    pub fn create_multisample_hdr(
+        width: i32,
+        height: i32,
+        samples: i32,
+    ) -> Result<Self, FramebufferError> {
+        Self::create_with_color_buffer(width, height, samples, |len| {
+            ColorBuffer::Rgba32F(Attachment::new(AttachmentFormat::Rgba32F, len))
+        })
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        matches!(self.color_buffer, ColorBuffer::Rgba32F(_))
+    }
+
+    // A framebuffer for depth passes such as shadow maps. It has no color buffer, so color writes
+    // are disabled and reading attachment 0 fails.
+    pub fn create_depth_only(width: i32, height: i32) -> Result<Self, FramebufferError> {
+        let mut framebuffer =
+            Self::create_with_color_buffer(width, height, 1, |_| ColorBuffer::None)?;
+        framebuffer.set_color_write(false);
+        Ok(framebuffer)
+    }
+
+    fn has_color_buffer(&self) -> bool {
+        !matches!(self.color_buffer, ColorBuffer::None)
+    }
+
     fn calc_offset(&self, x: i32, y: i32) -> Result<usize, FramebufferError> {
         if x < 0 || y < 0 || x >= self.width || y >= self.height {
             return Err(FramebufferError::BadPosition);
         }
-        Ok((y * self.width + x) as usize)
+        Ok(((y * self.width + x) * self.samples) as usize)
+    }
+
+    pub fn samples(&self) -> i32 {
+        self.samples
+    }
+
+    pub(crate) fn sample_pattern(&self) -> &'static [(i32, i32)] {
+        match self.samples {
+            2 => &SAMPLE_PATTERN_2,
+            4 => &SAMPLE_PATTERN_4,
+            8 => &SAMPLE_PATTERN_8,
+            _ => &SAMPLE_PATTERN_1,
+        }
+    }
+
+    // Adds a color attachment that shaders write with `Shader::fragment_targets` and returns its
+    // index. Attachment 0 is the color buffer.
+    pub fn add_attachment(&mut self, format: AttachmentFormat) -> Result<usize, FramebufferError> {
+        if self.attachment_count() >= MAX_COLOR_ATTACHMENTS {
+            return Err(FramebufferError::BadAttachment);
+        }
+        self.attachments
+            .push(Attachment::new(format, self.depth_buffer.len()));
+        Ok(self.attachments.len())
+    }
+
+    pub fn attachment_count(&self) -> usize {
+        self.attachments.len() + 1
+    }
+
+    pub fn attachment_format(&self, index: usize) -> Option<AttachmentFormat> {
+        match (index, &self.color_buffer) {
+            (0, ColorBuffer::Rgba8(_)) => Some(AttachmentFormat::Rgba8),
+            (0, ColorBuffer::Rgba32F(_)) => Some(AttachmentFormat::Rgba32F),
+            (0, ColorBuffer::None) => None,
+            _ => self.attachments.get(index - 1).map(|a| a.format),
+        }
+    }
+
+    pub fn clear_attachment_with(
+        &mut self,
+        index: usize,
+        color: &Colorf,
+    ) -> Result<(), FramebufferError> {
+        let color_space = self.color_space;
+        match index {
+            0 => match &mut self.color_buffer {
+                ColorBuffer::Rgba8(buffer) => buffer.fill(color_space.encode(color)),
+                ColorBuffer::Rgba32F(buffer) => buffer.fill(color),
+                ColorBuffer::None => return Err(FramebufferError::BadAttachment),
+            },
+            _ => self
+                .attachments
+                .get_mut(index - 1)
+                .ok_or(FramebufferError::BadAttachment)?
+                .fill(color),
+        }
+        Ok(())
+    }
+
+    // Reads the first sample of a pixel of the attachment.
+    pub fn get_attachment(&self, index: usize, x: i32, y: i32) -> Result<Colorf, FramebufferError> {
+        let offset = self.calc_offset(x, y)?;
+        match index {
+            0 if !self.has_color_buffer() => Err(FramebufferError::BadAttachment),
+            0 => Ok(self.sample_color(offset)),
+            _ => Ok(self
+                .attachments
+                .get(index - 1)
+                .ok_or(FramebufferError::BadAttachment)?
+                .get(offset)),
+        }
+    }
+
+    // The color of a sample as a linear value, framebuffers without a color buffer read as 0.
+    fn sample_color(&self, offset: usize) -> Colorf {
+        match &self.color_buffer {
+            ColorBuffer::Rgba8(buffer) => self.color_space.decode(&buffer[offset]),
+            ColorBuffer::Rgba32F(buffer) => buffer.get(offset),
+            ColorBuffer::None => Colorf::zeros(),
+        }
+    }
+
+    // Writes every sample of a pixel of the attachment.
+    pub fn set_attachment(
+        &mut self,
+        index: usize,
+        x: i32,
+        y: i32,
+        color: &Colorf,
+    ) -> Result<(), FramebufferError> {
+        let offset = self.calc_offset(x, y)?;
+        let samples = offset..offset + self.samples as usize;
+        let color_space = self.color_space;
+        match index {
+            0 => match &mut self.color_buffer {
+                ColorBuffer::Rgba8(buffer) => buffer[samples].fill(color_space.encode(color)),
+                ColorBuffer::Rgba32F(buffer) => {
+                    samples.for_each(|offset| buffer.set(offset, color))
+                }
+                ColorBuffer::None => return Err(FramebufferError::BadAttachment),
+            },
+            _ => {
+                let attachment = self
+                    .attachments
+                    .get_mut(index - 1)
+                    .ok_or(FramebufferError::BadAttachment)?;
+                samples.for_each(|offset| attachment.set(offset, color));
+            }
+        }
+        Ok(())
+    }
+
+    // Exchanges the color buffers of two framebuffers of the same size and format.
+    pub(crate) fn swap_color_buffers(&mut self, other: &mut Framebuffer) {
+        std::mem::swap(&mut self.color_buffer, &mut other.color_buffer);
     }
 
     pub fn clear_color_with(&mut self, color: &Color) {
-        self.color_buffer.fill(*color);
+        let color_space = self.color_space;
+        match &mut self.color_buffer {
+            ColorBuffer::Rgba8(buffer) => buffer.fill(*color),
+            ColorBuffer::Rgba32F(buffer) => buffer.fill(&color_space.decode(color)),
+            ColorBuffer::None => {}
+        }
     }
 
     pub fn clear_color(&mut self) {
@@ -69,24 +349,160 @@ impl Framebuffer {
         self.clear_depth_with(f32::MIN);
     }
 
+    pub fn clear_stencil_with(&mut self, stencil: u8) {
+        self.stencil_buffer.fill(stencil);
+    }
+
+    pub fn clear_stencil(&mut self) {
+        self.clear_stencil_with(0);
+    }
+
     pub fn set_color(&mut self, x: i32, y: i32, color: &Color) {
         if let Ok(offset) = self.calc_offset(x, y) {
-            self.color_buffer[offset] = *color;
+            let samples = offset..offset + self.samples as usize;
+            let color_space = self.color_space;
+            match &mut self.color_buffer {
+                ColorBuffer::Rgba8(buffer) => buffer[samples].fill(*color),
+                ColorBuffer::Rgba32F(buffer) => {
+                    let color = color_space.decode(color);
+                    samples.for_each(|offset| buffer.set(offset, &color));
+                }
+                ColorBuffer::None => {}
+            }
         }
     }
 
+    // The RGBA8 color buffer. HDR framebuffers have none and are read with `get_attachment`,
+    // depth-only framebuffers have no color buffer at all.
+    fn rgba8_buffer(&self) -> Result<&[Color], FramebufferError> {
+        match &self.color_buffer {
+            ColorBuffer::Rgba8(buffer) => Ok(buffer),
+            _ => Err(FramebufferError::BadAttachment),
+        }
+    }
+
+    fn rgba8_pixels(&self) -> Result<&[Color], FramebufferError> {
+        if self.samples > 1 {
+            return Err(FramebufferError::BadSampleCount);
+        }
+        self.rgba8_buffer()
+    }
+
     pub fn get_color(&self, x: i32, y: i32) -> Result<&Color, FramebufferError> {
         let offset = self.calc_offset(x, y)?;
-        Ok(&(self.color_buffer[offset]))
+        Ok(&(self.rgba8_buffer()?[offset]))
+    }
+
+    // When disabled, fragments only update the depth and stencil buffers.
+    pub fn set_color_write(&mut self, enable: bool) {
+        self.color_write = enable;
+    }
+
+    pub fn color_write(&self) -> bool {
+        self.color_write
+    }
+
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
     }
 
     pub fn set_depth_test(&mut self, enable: bool) {
-        self.depth_test = enable;
+        self.depth_state.test_enabled = enable;
+    }
+
+    pub fn set_depth_state(&mut self, state: &DepthState) {
+        self.depth_state = *state;
+    }
+
+    pub fn depth_state(&self) -> &DepthState {
+        &self.depth_state
+    }
+
+    pub fn set_stencil_state(&mut self, state: &StencilState) {
+        self.stencil_state = *state;
+    }
+
+    pub fn stencil_state(&self) -> &StencilState {
+        &self.stencil_state
+    }
+
+    pub fn set_blend_state(&mut self, state: &BlendState) {
+        self.blend_state = *state;
+    }
+
+    pub fn blend_state(&self) -> &BlendState {
+        &self.blend_state
+    }
+
+    pub fn set_viewport(&mut self, viewport: &Viewport) {
+        self.viewport = *viewport;
+    }
+
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    pub fn set_scissor(&mut self, scissor: Option<&Scissor>) {
+        self.scissor = scissor.copied();
+    }
+
+    pub fn scissor(&self) -> Option<&Scissor> {
+        self.scissor.as_ref()
+    }
+
+    // Inclusive pixel bounds that primitives are drawn into: the part of the viewport inside the
+    // framebuffer and the scissor rectangle. The bounds are empty when min > max.
+    pub(crate) fn draw_bounds(&self) -> (Vec2i, Vec2i) {
+        let viewport = &self.viewport;
+        let (min, max) = Scissor::new(0, 0, self.width, self.height).intersect(
+            &Vec2i::new(viewport.x, viewport.y),
+            &Vec2i::new(
+                viewport.x + viewport.width - 1,
+                viewport.y + viewport.height - 1,
+            ),
+        );
+        match &self.scissor {
+            Some(scissor) => scissor.intersect(&min, &max),
+            None => (min, max),
+        }
+    }
+
+    pub fn set_rasterizer_state(&mut self, state: &RasterizerState) {
+        self.rasterizer_state = *state;
+    }
+
+    pub fn rasterizer_state(&self) -> &RasterizerState {
+        &self.rasterizer_state
+    }
+
+    pub fn stats(&self) -> &RasterizerStats {
+        &self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = RasterizerStats::default();
+    }
+
+    pub fn get_sample_color(
+        &self,
+        x: i32,
+        y: i32,
+        sample: i32,
+    ) -> Result<&Color, FramebufferError> {
+        if sample < 0 || sample >= self.samples {
+            return Err(FramebufferError::BadPosition);
+        }
+        let offset = self.calc_offset(x, y)?;
+        Ok(&(self.rgba8_buffer()?[offset + sample as usize]))
     }
 
     pub fn set_depth(&mut self, x: i32, y: i32, depth: f32) {
         if let Ok(offset) = self.calc_offset(x, y) {
-            self.depth_buffer[offset] = depth;
+            self.depth_buffer[offset..offset + self.samples as usize].fill(depth);
         }
     }
 
@@ -98,54 +514,316 @@ impl Framebuffer {
         }
     }
 
+    pub fn set_stencil(&mut self, x: i32, y: i32, stencil: u8) {
+        if let Ok(offset) = self.calc_offset(x, y) {
+            self.stencil_buffer[offset..offset + self.samples as usize].fill(stencil);
+        }
+    }
+
+    pub fn get_stencil(&self, x: i32, y: i32) -> u8 {
+        if let Ok(offset) = self.calc_offset(x, y) {
+            self.stencil_buffer[offset]
+        } else {
+            0
+        }
+    }
+
     pub fn set_color_with_depth(&mut self, x: i32, y: i32, depth: f32, color: &Color) {
-        if self.depth_test {
-            if ((-1f32 - f32::EPSILON)..=(1f32 + f32::EPSILON)).contains(&depth)
-                && depth > self.get_depth(x, y)
-            {
-                self.set_color(x, y, color);
-                self.set_depth(x, y, depth);
+        let color = self.color_space.decode(color);
+        self.rows_mut().set_color_with_depth(x, y, depth, &color);
+    }
+
+    pub(crate) fn rows_mut(&mut self) -> FramebufferRows<'_> {
+        let sample_pattern = self.sample_pattern();
+        FramebufferRows {
+            color_buffer: self.color_buffer.rows_mut(),
+            attachments: self
+                .attachments
+                .iter_mut()
+                .map(|attachment| AttachmentRows {
+                    format: attachment.format,
+                    data: &mut attachment.data,
+                })
+                .collect(),
+            depth_buffer: &mut self.depth_buffer,
+            stencil_buffer: &mut self.stencil_buffer,
+            samples: self.samples,
+            color_write: self.color_write,
+            color_space: self.color_space,
+            sample_pattern,
+            depth_state: self.depth_state,
+            stencil_state: self.stencil_state,
+            blend_state: self.blend_state,
+            viewport: self.viewport,
+            width: self.width,
+            y_begin: 0,
+            y_end: self.height,
+        }
+    }
+
+    pub(crate) fn rows_chunks_mut(&mut self, rows_per_chunk: i32) -> Vec<FramebufferRows<'_>> {
+        let chunk_size = (self.width * rows_per_chunk * self.samples) as usize;
+        let sample_pattern = self.sample_pattern();
+        let chunk_count = self.depth_buffer.len().div_ceil(chunk_size);
+        let mut attachments: Vec<Vec<AttachmentRows>> =
+            (0..chunk_count).map(|_| Vec::new()).collect();
+        for attachment in self.attachments.iter_mut() {
+            let size = chunk_size * attachment.format.bytes_per_pixel();
+            for (rows, data) in attachments.iter_mut().zip(attachment.data.chunks_mut(size)) {
+                rows.push(AttachmentRows {
+                    format: attachment.format,
+                    data,
+                });
             }
-        } else {
-            self.set_color(x, y, color);
         }
+        self.color_buffer
+            .chunks_mut(chunk_size, chunk_count)
+            .into_iter()
+            .zip(attachments)
+            .zip(self.depth_buffer.chunks_mut(chunk_size))
+            .zip(self.stencil_buffer.chunks_mut(chunk_size))
+            .enumerate()
+            .map(
+                |(i, (((color_buffer, attachments), depth_buffer), stencil_buffer))| {
+                    let y_begin = i as i32 * rows_per_chunk;
+                    FramebufferRows {
+                        color_buffer,
+                        attachments,
+                        depth_buffer,
+                        stencil_buffer,
+                        samples: self.samples,
+                        color_write: self.color_write,
+                        color_space: self.color_space,
+                        sample_pattern,
+                        depth_state: self.depth_state,
+                        stencil_state: self.stencil_state,
+                        blend_state: self.blend_state,
+                        viewport: self.viewport,
+                        width: self.width,
+                        y_begin,
+                        y_end: (y_begin + rows_per_chunk).min(self.height),
+                    }
+                },
+            )
+            .collect()
     }
 
-    pub fn to_u8_ptr(&self) -> *const u8 {
-        self.color_buffer.as_ptr() as *const u8
+    // The raw accessors fail for framebuffers without an RGBA8 color buffer, and for multisample
+    // framebuffers whose samples have to be resolved into pixels first.
+    pub fn to_u8_ptr(&self) -> Result<*const u8, FramebufferError> {
+        Ok(self.rgba8_pixels()?.as_ptr() as *const u8)
     }
 
-    pub fn to_u8_slice(&self) -> &[u8] {
+    pub fn to_u8_slice(&self) -> Result<&[u8], FramebufferError> {
+        let ptr = self.to_u8_ptr()?;
         unsafe {
-            slice::from_raw_parts(
-                self.to_u8_ptr(),
+            Ok(slice::from_raw_parts(
+                ptr,
                 self.width as usize * self.height as usize * std::mem::size_of::<Color>(),
-            )
+            ))
         }
     }
 
-    pub fn to_u32_ptr(&self) -> *const u32 {
-        self.color_buffer.as_ptr() as *const u32
+    pub fn to_u32_ptr(&self) -> Result<*const u32, FramebufferError> {
+        Ok(self.rgba8_pixels()?.as_ptr() as *const u32)
     }
 
-    pub fn to_u32_slice(&self) -> &[u32] {
+    pub fn to_u32_slice(&self) -> Result<&[u32], FramebufferError> {
+        let ptr = self.to_u32_ptr()?;
         unsafe {
-            slice::from_raw_parts(
-                self.to_u32_ptr(),
+            Ok(slice::from_raw_parts(
+                ptr,
                 self.width as usize * self.height as usize,
-            )
+            ))
+        }
+    }
+
+    // Averages the samples of every pixel into the single-sampled `target`, sRGB colors are
+    // averaged as linear values and `target` is expected to use the same color space. HDR
+    // framebuffers resolve to HDR framebuffers. Every attachment is resolved as well, `target`
+    // must have attachments of the same formats.
+    pub fn resolve_to(&self, target: &mut Framebuffer) -> Result<(), FramebufferError> {
+        if target.width != self.width || target.height != self.height || target.samples != 1 {
+            return Err(FramebufferError::BadSize);
+        }
+        if target.attachments.len() != self.attachments.len()
+            || self
+                .attachments
+                .iter()
+                .zip(target.attachments.iter())
+                .any(|(src, dst)| src.format != dst.format)
+        {
+            return Err(FramebufferError::BadAttachment);
+        }
+        let samples = self.samples as usize;
+        match (&self.color_buffer, &mut target.color_buffer) {
+            (ColorBuffer::Rgba8(src), ColorBuffer::Rgba8(dst)) => {
+                self.resolve_rgba8(src, dst);
+            }
+            (ColorBuffer::Rgba32F(src), ColorBuffer::Rgba32F(dst)) => {
+                Self::resolve_attachment(src, dst, samples);
+            }
+            (ColorBuffer::None, ColorBuffer::None) => {}
+            _ => return Err(FramebufferError::BadAttachment),
+        }
+        for (src, dst) in self.attachments.iter().zip(target.attachments.iter_mut()) {
+            Self::resolve_attachment(src, dst, samples);
+        }
+        for (dst, src) in target
+            .depth_buffer
+            .iter_mut()
+            .zip(self.depth_buffer.chunks(samples))
+        {
+            *dst = src[0];
+        }
+        for (dst, src) in target
+            .stencil_buffer
+            .iter_mut()
+            .zip(self.stencil_buffer.chunks(samples))
+        {
+            *dst = src[0];
+        }
+        Ok(())
+    }
+
+    fn resolve_attachment(src: &Attachment, dst: &mut Attachment, samples: usize) {
+        let size = dst.format.bytes_per_pixel();
+        for offset in 0..dst.data.len() / size {
+            let sum: Colorf = (0..samples)
+                .map(|sample| src.get(offset * samples + sample))
+                .sum();
+            dst.set(offset, &(sum / samples as f32));
+        }
+    }
+
+    fn resolve_rgba8(&self, src: &[Color], dst: &mut [Color]) {
+        let samples = self.samples as usize;
+        for (dst, src) in dst.iter_mut().zip(src.chunks(samples)) {
+            *dst = match self.color_space {
+                ColorSpace::Linear => {
+                    let mut sum = [0u32; 4];
+                    for color in src {
+                        sum[0] += color.r as u32;
+                        sum[1] += color.g as u32;
+                        sum[2] += color.b as u32;
+                        sum[3] += color.a as u32;
+                    }
+                    let half = samples as u32 / 2;
+                    Color::new(
+                        ((sum[0] + half) / samples as u32) as u8,
+                        ((sum[1] + half) / samples as u32) as u8,
+                        ((sum[2] + half) / samples as u32) as u8,
+                        ((sum[3] + half) / samples as u32) as u8,
+                    )
+                }
+                ColorSpace::Srgb => {
+                    let sum: Colorf = src.iter().map(|color| self.color_space.decode(color)).sum();
+                    self.color_space.encode(&(sum / samples as f32))
+                }
+            };
+        }
+    }
+
+    pub fn resolve(&self) -> Framebuffer {
+        let mut target = match self.color_buffer {
+            ColorBuffer::Rgba8(_) => Framebuffer::create(self.width, self.height).unwrap(),
+            ColorBuffer::Rgba32F(_) => Framebuffer::create_hdr(self.width, self.height).unwrap(),
+            ColorBuffer::None => Framebuffer::create_depth_only(self.width, self.height).unwrap(),
+        };
+        for attachment in &self.attachments {
+            target.add_attachment(attachment.format).unwrap();
+        }
+        target.set_color_space(self.color_space);
+        self.resolve_to(&mut target).unwrap();
+        target
+    }
+
+    // Converts the linear colors of every sample with `tone_mapping`, then averages them into the
+    // single-sampled 8-bit `target`, encoded in the color space of `target`.
+    pub fn tone_map_to(
+        &self,
+        target: &mut Framebuffer,
+        tone_mapping: &ToneMapping,
+    ) -> Result<(), FramebufferError> {
+        if target.width != self.width || target.height != self.height || target.samples != 1 {
+            return Err(FramebufferError::BadSize);
+        }
+        if !self.has_color_buffer() {
+            return Err(FramebufferError::BadAttachment);
+        }
+        let color_space = target.color_space;
+        let dst = match &mut target.color_buffer {
+            ColorBuffer::Rgba8(buffer) => buffer,
+            _ => return Err(FramebufferError::BadAttachment),
+        };
+        let samples = self.samples as usize;
+        for (offset, dst) in dst.iter_mut().enumerate() {
+            let sum: Colorf = (0..samples)
+                .map(|sample| {
+                    let color = self.sample_color(offset * samples + sample);
+                    tone_mapping
+                        .map(&color.xyz())
+                        .push(color.w.clamp(0f32, 1f32))
+                })
+                .sum();
+            *dst = color_space.encode(&(sum / samples as f32));
         }
+        Ok(())
     }
 
+    pub fn tone_map(&self, tone_mapping: &ToneMapping) -> Framebuffer {
+        let mut target = Framebuffer::create(self.width, self.height).unwrap();
+        target.set_color_space(self.color_space);
+        self.tone_map_to(&mut target, tone_mapping).unwrap();
+        target
+    }
+
+    // HDR framebuffers are written with the default tone mapping.
     pub fn write(&self, filepath: impl AsRef<Path>) -> Result<(), FramebufferError> {
-        image_write(filepath, self.to_u8_slice(), self.width, self.height, 4).map_err(|e| e.into())
+        if self.is_hdr() {
+            return self.tone_map(&ToneMapping::default()).write(filepath);
+        }
+        if self.samples > 1 {
+            return self.resolve().write(filepath);
+        }
+        image_write(filepath, self.to_u8_slice()?, self.width, self.height, 4).map_err(|e| e.into())
     }
 
-    pub fn write_depth(&self, filepath: impl AsRef<Path>) -> Result<(), FramebufferError> {
-        let mut depth_buffer: Vec<u8> = vec![0; self.depth_buffer.len()];
-        for i in 0..self.depth_buffer.len() {
-            depth_buffer[i] = ((self.depth_buffer[i] / 2f32 + 0.5f32) * 255f32).round() as u8;
+    // Exports the first sample of every pixel of the attachment, float formats are clamped to
+    // [0, 1] and RG16 is written as RGB with an empty blue channel.
+    pub fn write_attachment(
+        &self,
+        index: usize,
+        filepath: impl AsRef<Path>,
+    ) -> Result<(), FramebufferError> {
+        if index == 0 {
+            return self.write(filepath);
         }
+        let attachment = self
+            .attachments
+            .get(index - 1)
+            .ok_or(FramebufferError::BadAttachment)?;
+        let comp = match attachment.format.channels() {
+            2 => 3,
+            channels => channels,
+        };
+        let data: Vec<u8> = (0..self.depth_buffer.len())
+            .step_by(self.samples as usize)
+            .flat_map(|offset| {
+                let color: Color = attachment.get(offset).into();
+                [color.r, color.g, color.b, color.a].into_iter().take(comp)
+            })
+            .collect();
+        image_write(filepath, &data, self.width, self.height, comp as i32).map_err(|e| e.into())
+    }
+
+    pub fn write_depth(&self, filepath: impl AsRef<Path>) -> Result<(), FramebufferError> {
+        let depth_buffer: Vec<u8> = self
+            .depth_buffer
+            .iter()
+            .step_by(self.samples as usize)
+            .map(|depth| ((depth / 2f32 + 0.5f32) * 255f32).round() as u8)
+            .collect();
         image_write(
             filepath,
             depth_buffer.as_slice(),
@@ -156,3 +834,136 @@ impl Framebuffer {
         .map_err(|e| e.into())
     }
 }
+
+// A mutable view of the rows [y_begin, y_end) of a framebuffer, addressed with framebuffer
+// coordinates. Disjoint views can be written from different threads.
+pub(crate) struct FramebufferRows<'a> {
+    color_buffer: ColorBufferRows<'a>,
+    attachments: Vec<AttachmentRows<'a>>,
+    depth_buffer: &'a mut [f32],
+    stencil_buffer: &'a mut [u8],
+    samples: i32,
+    pub(crate) sample_pattern: &'static [(i32, i32)],
+    color_write: bool,
+    color_space: ColorSpace,
+    depth_state: DepthState,
+    stencil_state: StencilState,
+    blend_state: BlendState,
+    viewport: Viewport,
+    width: i32,
+    pub(crate) y_begin: i32,
+    pub(crate) y_end: i32,
+}
+
+impl FramebufferRows<'_> {
+    fn calc_offset(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < self.y_begin || x >= self.width || y >= self.y_end {
+            return None;
+        }
+        Some((((y - self.y_begin) * self.width + x) * self.samples) as usize)
+    }
+
+    pub(crate) fn attachment_count(&self) -> usize {
+        self.attachments.len() + 1
+    }
+
+    pub(crate) fn set_color_with_depth(&mut self, x: i32, y: i32, depth: f32, color: &Colorf) {
+        let color = Some(*color);
+        if let Some(offset) = self.calc_offset(x, y) {
+            for sample in 0..self.samples as usize {
+                self.write_sample(offset + sample, depth, true, slice::from_ref(&color));
+            }
+        }
+    }
+
+    pub(crate) fn set_sample_color_with_depth(
+        &mut self,
+        x: i32,
+        y: i32,
+        sample: usize,
+        depth: f32,
+        front_facing: bool,
+        colors: &[Option<Colorf>],
+    ) {
+        if let Some(offset) = self.calc_offset(x, y) {
+            self.write_sample(offset + sample, depth, front_facing, colors);
+        }
+    }
+
+    // Runs the stencil and depth tests, then updates the stencil, depth and colors of the sample.
+    // `colors[i]` is written to attachment i, attachments without a color are left unchanged.
+    fn write_sample(
+        &mut self,
+        offset: usize,
+        depth: f32,
+        front_facing: bool,
+        colors: &[Option<Colorf>],
+    ) {
+        let stencil = &self.stencil_state;
+        if stencil.enabled && !stencil.test(front_facing, self.stencil_buffer[offset]) {
+            let op = stencil.face(front_facing).fail_op;
+            self.stencil_buffer[offset] = stencil.update(op, self.stencil_buffer[offset]);
+            return;
+        }
+        if self.depth_state.test_enabled {
+            let depth = self.viewport.clamp_depth(depth);
+            if !self
+                .depth_state
+                .compare
+                .test(depth, self.depth_buffer[offset])
+            {
+                if stencil.enabled {
+                    let op = stencil.face(front_facing).depth_fail_op;
+                    self.stencil_buffer[offset] = stencil.update(op, self.stencil_buffer[offset]);
+                }
+                return;
+            }
+            if self.depth_state.write_enabled {
+                self.depth_buffer[offset] = depth;
+            }
+        }
+        if stencil.enabled {
+            let op = stencil.face(front_facing).pass_op;
+            self.stencil_buffer[offset] = stencil.update(op, self.stencil_buffer[offset]);
+        }
+        if !self.color_write {
+            return;
+        }
+        if let Some(color) = &colors[0] {
+            self.write_color(offset, color);
+        }
+        for (attachment, color) in self.attachments.iter_mut().zip(&colors[1..]) {
+            match color {
+                Some(color) if self.blend_state.enabled => {
+                    let dst = attachment.get(offset);
+                    attachment.set(offset, &self.blend_state.blend(color, &dst));
+                }
+                Some(color) => attachment.set(offset, color),
+                None => {}
+            }
+        }
+    }
+
+    fn write_color(&mut self, offset: usize, color: &Colorf) {
+        match &mut self.color_buffer {
+            ColorBufferRows::Rgba8(buffer) => {
+                buffer[offset] = if self.blend_state.enabled {
+                    let dst = self.color_space.decode(&buffer[offset]);
+                    self.color_space
+                        .encode(&self.blend_state.blend(color, &dst))
+                } else {
+                    self.color_space.encode(color)
+                };
+            }
+            ColorBufferRows::Rgba32F(buffer) => {
+                if self.blend_state.enabled {
+                    let dst = buffer.get(offset);
+                    buffer.set(offset, &self.blend_state.blend(color, &dst));
+                } else {
+                    buffer.set(offset, color);
+                }
+            }
+            ColorBufferRows::None => {}
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{Colorf, Framebuffer, FramebufferError, PostProcessPass, Vec3};
+
+// Distances in pixels between the luma samples taken along an edge to find its ends.
+const SEARCH_STEPS: [f32; 12] = [
+    1f32, 1f32, 1f32, 1f32, 1f32, 1.5f32, 2f32, 2f32, 2f32, 2f32, 4f32, 8f32,
+];
+
+// Colors of a framebuffer and their luma, addressed with continuous coordinates in pixels where
+// pixel centers are at (x + 0.5, y + 0.5).
+struct Pixels {
+    width: i32,
+    height: i32,
+    colors: Vec<Colorf>,
+    lumas: Vec<f32>,
+}
+
+impl Pixels {
+    fn read(framebuffer: &Framebuffer) -> Result<Self, FramebufferError> {
+        let mut colors = Vec::with_capacity((framebuffer.width * framebuffer.height) as usize);
+        for y in 0..framebuffer.height {
+            for x in 0..framebuffer.width {
+                colors.push(framebuffer.get_attachment(0, x, y)?);
+            }
+        }
+        let lumas = colors.iter().map(luma).collect();
+        Ok(Pixels {
+            width: framebuffer.width,
+            height: framebuffer.height,
+            colors,
+            lumas,
+        })
+    }
+
+    fn offset(&self, x: i32, y: i32) -> usize {
+        (y.clamp(0, self.height - 1) * self.width + x.clamp(0, self.width - 1)) as usize
+    }
+
+    fn luma(&self, x: i32, y: i32) -> f32 {
+        self.lumas[self.offset(x, y)]
+    }
+
+    // Bilinear weights and offsets of the 4 pixels around (x, y).
+    fn bilinear(&self, x: f32, y: f32) -> [(f32, usize); 4] {
+        let x = x - 0.5f32;
+        let y = y - 0.5f32;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (xt, yt) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        [
+            ((1f32 - xt) * (1f32 - yt), self.offset(x0, y0)),
+            (xt * (1f32 - yt), self.offset(x0 + 1, y0)),
+            ((1f32 - xt) * yt, self.offset(x0, y0 + 1)),
+            (xt * yt, self.offset(x0 + 1, y0 + 1)),
+        ]
+    }
+
+    fn sample_luma(&self, x: f32, y: f32) -> f32 {
+        self.bilinear(x, y)
+            .iter()
+            .map(|(weight, offset)| self.lumas[*offset] * weight)
+            .sum()
+    }
+
+    fn sample_color(&self, x: f32, y: f32) -> Colorf {
+        self.bilinear(x, y)
+            .iter()
+            .map(|(weight, offset)| self.colors[*offset] * *weight)
+            .sum()
+    }
+}
+
+// Perceptual luma of a linear color, approximated with a square root.
+fn luma(color: &Colorf) -> f32 {
+    let color = color.xyz().map(|value| value.clamp(0f32, 1f32));
+    color.dot(&Vec3::new(0.299f32, 0.587f32, 0.114f32)).sqrt()
+}
+
+// Fast approximate anti-aliasing. Edges are detected from the luma contrast around every pixel,
+// then the pixel is blended with its neighbor across the edge according to its distance to the
+// ends of the edge, and according to the local contrast for details smaller than a pixel.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Fxaa {
+    // Minimum luma range around a pixel, relative to its brightest neighbor, to be an edge.
+    pub edge_threshold: f32,
+    // Minimum absolute luma range to be an edge, which skips dark areas.
+    pub edge_threshold_min: f32,
+    // Amount of sub-pixel blending, from 0 to 1.
+    pub subpixel_quality: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Fxaa {
+            edge_threshold: 0.125f32,
+            edge_threshold_min: 0.0312f32,
+            subpixel_quality: 0.75f32,
+        }
+    }
+}
+
+impl Fxaa {
+    fn filter(&self, pixels: &Pixels, x: i32, y: i32) -> Colorf {
+        let center = pixels.luma(x, y);
+        let up = pixels.luma(x, y - 1);
+        let down = pixels.luma(x, y + 1);
+        let left = pixels.luma(x - 1, y);
+        let right = pixels.luma(x + 1, y);
+        let luma_min = center.min(up).min(down).min(left).min(right);
+        let luma_max = center.max(up).max(down).max(left).max(right);
+        let range = luma_max - luma_min;
+        let pixel = pixels.colors[pixels.offset(x, y)];
+        if range < self.edge_threshold_min.max(luma_max * self.edge_threshold) {
+            return pixel;
+        }
+        let up_left = pixels.luma(x - 1, y - 1);
+        let up_right = pixels.luma(x + 1, y - 1);
+        let down_left = pixels.luma(x - 1, y + 1);
+        let down_right = pixels.luma(x + 1, y + 1);
+
+        let edge_horizontal = (up_left + down_left - 2f32 * left).abs()
+            + 2f32 * (up + down - 2f32 * center).abs()
+            + (up_right + down_right - 2f32 * right).abs();
+        let edge_vertical = (up_left + up_right - 2f32 * up).abs()
+            + 2f32 * (left + right - 2f32 * center).abs()
+            + (down_left + down_right - 2f32 * down).abs();
+        let horizontal = edge_horizontal >= edge_vertical;
+
+        // The neighbor across the edge with the steepest gradient, one pixel along `normal`.
+        let (luma1, luma2) = if horizontal {
+            (up, down)
+        } else {
+            (left, right)
+        };
+        let gradient1 = luma1 - center;
+        let gradient2 = luma2 - center;
+        let steepest1 = gradient1.abs() >= gradient2.abs();
+        let gradient_scaled = 0.25f32 * gradient1.abs().max(gradient2.abs());
+        let (normal, local_average) = if steepest1 {
+            (-1f32, 0.5f32 * (luma1 + center))
+        } else {
+            (1f32, 0.5f32 * (luma2 + center))
+        };
+
+        // Walks along the edge, halfway between the pixel and its neighbor, in both directions
+        // until the luma differs from the local average.
+        let (mut px, mut py) = (x as f32 + 0.5f32, y as f32 + 0.5f32);
+        let (step_x, step_y) = if horizontal {
+            py += normal * 0.5f32;
+            (1f32, 0f32)
+        } else {
+            px += normal * 0.5f32;
+            (0f32, 1f32)
+        };
+        let (mut x1, mut y1) = (px, py);
+        let (mut x2, mut y2) = (px, py);
+        let mut delta1 = 0f32;
+        let mut delta2 = 0f32;
+        let mut reached1 = false;
+        let mut reached2 = false;
+        for step in SEARCH_STEPS {
+            if !reached1 {
+                x1 -= step_x * step;
+                y1 -= step_y * step;
+                delta1 = pixels.sample_luma(x1, y1) - local_average;
+                reached1 = delta1.abs() >= gradient_scaled;
+            }
+            if !reached2 {
+                x2 += step_x * step;
+                y2 += step_y * step;
+                delta2 = pixels.sample_luma(x2, y2) - local_average;
+                reached2 = delta2.abs() >= gradient_scaled;
+            }
+            if reached1 && reached2 {
+                break;
+            }
+        }
+        let distance1 = if horizontal { px - x1 } else { py - y1 };
+        let distance2 = if horizontal { x2 - px } else { y2 - py };
+        let (distance, delta_end) = if distance1 < distance2 {
+            (distance1, delta1)
+        } else {
+            (distance2, delta2)
+        };
+        // Only blend when the closest end of the edge moves away from the luma of the pixel.
+        let center_smaller = center < local_average;
+        let edge_offset = if (delta_end < 0f32) != center_smaller {
+            0.5f32 - distance / (distance1 + distance2)
+        } else {
+            0f32
+        };
+
+        let average =
+            (2f32 * (up + down + left + right) + up_left + up_right + down_left + down_right)
+                / 12f32;
+        let subpixel = ((average - center).abs() / range).clamp(0f32, 1f32);
+        let subpixel = (-2f32 * subpixel + 3f32) * subpixel * subpixel;
+        let subpixel_offset = subpixel * subpixel * self.subpixel_quality;
+
+        let offset = edge_offset.max(subpixel_offset) * normal;
+        if horizontal {
+            pixels.sample_color(x as f32 + 0.5f32, y as f32 + 0.5f32 + offset)
+        } else {
+            pixels.sample_color(x as f32 + 0.5f32 + offset, y as f32 + 0.5f32)
+        }
+    }
+}
+
+impl PostProcessPass for Fxaa {
+    fn apply(
+        &self,
+        source: &Framebuffer,
+        target: &mut Framebuffer,
+    ) -> Result<(), FramebufferError> {
+        let pixels = Pixels::read(source)?;
+        for y in 0..source.height {
+            for x in 0..source.width {
+                target.set_attachment(0, x, y, &self.filter(&pixels, x, y))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,27 +1,91 @@
+mod attachment;
+mod blend;
+mod bloom;
+mod camera;
+mod clip;
 mod color;
+mod deferred;
+mod depth;
 mod fps;
 mod framebuffer;
+mod fxaa;
 mod image_rw;
+mod light;
+mod material;
 mod model;
+mod normal_map;
+mod pbr;
+mod post_process;
 mod primitive;
+mod rasterizer;
+mod shader;
+mod shadow;
+mod ssao;
+mod stencil;
 mod texture;
+mod tone_mapping;
+mod viewport;
 
+pub use attachment::AttachmentFormat;
+pub use attachment::MAX_COLOR_ATTACHMENTS;
+pub use blend::BlendEquation;
+pub use blend::BlendFactor;
+pub use blend::BlendState;
+pub use bloom::Bloom;
+pub use camera::Camera;
+pub use camera::Projection;
+pub use color::linear_to_srgb;
+pub use color::srgb_to_linear;
 pub use color::Color;
+pub use color::ColorSpace;
 pub use color::Colorf;
+pub use deferred::draw_deferred_lighting;
+pub use deferred::GBuffer;
+pub use deferred::GBufferShader;
+pub use deferred::GBUFFER_ALBEDO;
+pub use deferred::GBUFFER_NORMAL;
+pub use deferred::GBUFFER_SPECULAR;
+pub use depth::CompareFunction;
+pub use depth::DepthState;
 pub use fps::Fps;
 pub use fps::FpsRet;
 pub use framebuffer::Framebuffer;
 pub use framebuffer::FramebufferError;
+pub use fxaa::Fxaa;
 pub use image_rw::image_read;
 pub use image_rw::image_write;
 pub use image_rw::ImageReadError;
 pub use image_rw::ImageWriteError;
+pub use light::Attenuation;
+pub use light::Light;
+pub use material::Material;
 pub use model::Model;
+pub use normal_map::NormalMap;
+pub use normal_map::NormalMapSpace;
+pub use pbr::PbrMaterial;
+pub use post_process::PostProcess;
+pub use post_process::PostProcessPass;
 pub use primitive::draw_line;
 pub use primitive::draw_triangle;
+pub use primitive::draw_triangles;
+pub use rasterizer::CullMode;
+pub use rasterizer::FrontFace;
+pub use rasterizer::RasterizerState;
+pub use rasterizer::RasterizerStats;
+pub use shader::Shader;
+pub use shader::Varying;
+pub use shadow::ShadowMap;
+pub use ssao::Ssao;
+pub use stencil::StencilFaceState;
+pub use stencil::StencilOperation;
+pub use stencil::StencilState;
 pub use texture::Texture2D;
 pub use texture::Texture2DFilterMode;
 pub use texture::Texture2DWrapMode;
+pub use tone_mapping::ToneMapOperator;
+pub use tone_mapping::ToneMapping;
+pub use viewport::Scissor;
+pub use viewport::Viewport;
 
 pub type Vec2i = nalgebra::Vector2<i32>;
 pub type Vec3i = nalgebra::Vector3<i32>;
@@ -29,6 +93,7 @@ pub type Vec4i = nalgebra::Vector4<i32>;
 
 pub type Mat2x3 = nalgebra::Matrix2x3<f32>;
 pub type Mat3 = nalgebra::Matrix3<f32>;
+pub type Mat4 = nalgebra::Matrix4<f32>;
 
 pub type Vec2 = nalgebra::Vector2<f32>;
 pub type Vec3 = nalgebra::Vector3<f32>;
@@ -0,0 +1,241 @@
+use crate::Vec3;
+
+// Coefficients of the distance attenuation 1 / (constant + linear * d + quadratic * d^2).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation {
+            constant: 1f32,
+            linear: 0f32,
+            quadratic: 1f32,
+        }
+    }
+}
+
+impl Attenuation {
+    pub fn new(constant: f32, linear: f32, quadratic: f32) -> Self {
+        Attenuation {
+            constant,
+            linear,
+            quadratic,
+        }
+    }
+
+    pub fn factor(&self, distance: f32) -> f32 {
+        1f32 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+            .max(f32::EPSILON)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Light {
+    // `direction` points from the light towards the scene.
+    Directional {
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+    },
+    Point {
+        position: Vec3,
+        color: Vec3,
+        intensity: f32,
+        attenuation: Attenuation,
+    },
+    // Full intensity inside `inner_angle` of `direction`, fading out to 0 at `outer_angle`, both
+    // are half angles in radians.
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        attenuation: Attenuation,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl Light {
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        Light::Directional {
+            direction: direction.normalize(),
+            color,
+            intensity,
+        }
+    }
+
+    pub fn point(position: Vec3, color: Vec3, intensity: f32) -> Self {
+        Light::Point {
+            position,
+            color,
+            intensity,
+            attenuation: Attenuation::default(),
+        }
+    }
+
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Light::Spot {
+            position,
+            direction: direction.normalize(),
+            color,
+            intensity,
+            attenuation: Attenuation::default(),
+            inner_angle,
+            outer_angle,
+        }
+    }
+
+    // Replaces the attenuation of point and spot lights, directional lights are not attenuated.
+    pub fn with_attenuation(self, attenuation: Attenuation) -> Self {
+        match self {
+            Light::Directional { .. } => self,
+            Light::Point {
+                position,
+                color,
+                intensity,
+                ..
+            } => Light::Point {
+                position,
+                color,
+                intensity,
+                attenuation,
+            },
+            Light::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                inner_angle,
+                outer_angle,
+                ..
+            } => Light::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                attenuation,
+                inner_angle,
+                outer_angle,
+            },
+        }
+    }
+
+    // Returns the unit vector from `position` towards the light and the light reaching it. A point
+    // or spot light at `position` itself has no direction and lights nothing.
+    pub fn incident(&self, position: &Vec3) -> (Vec3, Vec3) {
+        match self {
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => (-direction, color * *intensity),
+            Light::Point {
+                position: light_position,
+                color,
+                intensity,
+                attenuation,
+            } => {
+                let to_light = light_position - position;
+                let distance = to_light.norm();
+                if distance <= f32::EPSILON {
+                    return (Vec3::y(), Vec3::zeros());
+                }
+                (
+                    to_light / distance,
+                    color * *intensity * attenuation.factor(distance),
+                )
+            }
+            Light::Spot {
+                position: light_position,
+                direction,
+                color,
+                intensity,
+                attenuation,
+                inner_angle,
+                outer_angle,
+            } => {
+                let to_light = light_position - position;
+                let distance = to_light.norm();
+                if distance <= f32::EPSILON {
+                    return (Vec3::y(), Vec3::zeros());
+                }
+                let to_light = to_light / distance;
+                let cos_angle = (-to_light).dot(direction);
+                let cos_inner = inner_angle.cos();
+                let cos_outer = outer_angle.cos();
+                let cone = if cos_inner > cos_outer {
+                    let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0f32, 1f32);
+                    t * t * (3f32 - 2f32 * t)
+                } else if cos_angle >= cos_outer {
+                    1f32
+                } else {
+                    0f32
+                };
+                (
+                    to_light,
+                    color * *intensity * attenuation.factor(distance) * cone,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_light() {
+        let color = Vec3::new(1f32, 0.5f32, 0.25f32);
+        let light = Light::directional(Vec3::new(0f32, -2f32, 0f32), color, 2f32);
+        let (to_light, radiance) = light.incident(&Vec3::new(5f32, 0f32, 5f32));
+        assert_relative_eq!(to_light, Vec3::y());
+        assert_relative_eq!(radiance, color * 2f32);
+
+        let light = Light::point(Vec3::new(0f32, 2f32, 0f32), color, 2f32);
+        let (to_light, radiance) = light.incident(&Vec3::zeros());
+        assert_relative_eq!(to_light, Vec3::y());
+        assert_relative_eq!(radiance, color * 2f32 / 5f32);
+        let light = light.with_attenuation(Attenuation::new(1f32, 0f32, 0f32));
+        assert_relative_eq!(light.incident(&Vec3::zeros()).1, color * 2f32);
+
+        let light = Light::spot(
+            Vec3::new(0f32, 1f32, 0f32),
+            -Vec3::y(),
+            color,
+            1f32,
+            std::f32::consts::FRAC_PI_8,
+            std::f32::consts::FRAC_PI_4,
+        )
+        .with_attenuation(Attenuation::new(1f32, 0f32, 0f32));
+        // Inside the inner cone, between the cones and outside of the outer cone.
+        assert_relative_eq!(light.incident(&Vec3::zeros()).1, color);
+        let between = light.incident(&Vec3::new(0.6f32, 0f32, 0f32)).1;
+        assert!(between.x > 0f32 && between.x < 1f32);
+        assert_relative_eq!(
+            light.incident(&Vec3::new(2f32, 0f32, 0f32)).1,
+            Vec3::zeros()
+        );
+
+        // Shading the position of the light itself doesn't produce NaN.
+        let point = Light::point(Vec3::new(0f32, 1f32, 0f32), color, 1f32);
+        for light in [light, point] {
+            let (to_light, radiance) = light.incident(&Vec3::new(0f32, 1f32, 0f32));
+            assert_relative_eq!(to_light.norm(), 1f32);
+            assert_relative_eq!(radiance, Vec3::zeros());
+        }
+    }
+}
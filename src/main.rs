@@ -1,37 +1,230 @@
-use minifb::{Key, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
 use std::slice;
 #[allow(unused_imports)]
 use tinyrenderer_rs::{draw_line, draw_triangle};
-use tinyrenderer_rs::{Color, Fps, FpsRet, Framebuffer, Model, Texture2D, Vec3};
-
-#[allow(unused_variables)]
-fn draw(framebuffer: &mut Framebuffer, model: &Model, texture: &Texture2D) {
-    let verts = &model.verts;
-    let uvs = &model.uvs;
-    let norms = &model.norms;
-    let light_dir = Vec3::new(0f32, 0f32, -1f32);
-    let light_intensity = 1f32;
-    for i in 0..(verts.len() / 3) {
-        draw_triangle(
-            framebuffer,
-            texture,
-            &verts[i * 3],
-            &verts[i * 3 + 1],
-            &verts[i * 3 + 2],
-            &uvs[i * 3],
-            &uvs[i * 3 + 1],
-            &uvs[i * 3 + 2],
-            &norms[i * 3],
-            &norms[i * 3 + 1],
-            &norms[i * 3 + 2],
-            &light_dir,
-            light_intensity,
-        );
+use tinyrenderer_rs::{
+    draw_triangles, Bloom, Camera, Color, ColorSpace, Colorf, CullMode, Fps, FpsRet, Framebuffer,
+    FrontFace, Fxaa, Light, Mat4, Material, Model, NormalMap, NormalMapSpace, PbrMaterial,
+    PostProcess, RasterizerState, Shader, ShadowMap, Ssao, Texture2D, ToneMapOperator, ToneMapping,
+    Vec2, Vec3, Vec4,
+};
+
+struct Mesh {
+    model: Model,
+    diffuse: Texture2D,
+    normal_map: Texture2D,
+    specular: Option<Texture2D>,
+    glow: Option<Texture2D>,
+}
+
+impl Mesh {
+    fn load(dir: &str, name: &str) -> Self {
+        Mesh {
+            model: Model::load(format!("{}/{}.obj", dir, name)).unwrap(),
+            diffuse: Texture2D::load_srgb(format!("{}/{}_diffuse.png", dir, name)).unwrap(),
+            normal_map: Texture2D::load(format!("{}/{}_nm_tangent.png", dir, name)).unwrap(),
+            specular: Texture2D::load(format!("{}/{}_spec.png", dir, name)).ok(),
+            glow: Texture2D::load_srgb(format!("{}/{}_glow.png", dir, name)).ok(),
+        }
+    }
+
+    fn material(&self) -> Material<'_> {
+        let mut material = Material::new(&self.diffuse);
+        material.specular = self.specular.as_ref();
+        material.glow = self.glow.as_ref();
+        material
+    }
+
+    // The assets have no metallic-roughness maps, so they are previewed as uniformly rough
+    // dielectrics.
+    fn pbr_material(&self) -> PbrMaterial<'_> {
+        let mut material = PbrMaterial::new(&Colorf::repeat(1f32), 0f32, PBR_ROUGHNESS)
+            .with_base_color(&self.diffuse);
+        if let Some(glow) = &self.glow {
+            material = material.with_emissive(glow, &Vec3::repeat(1f32));
+        }
+        material.ambient = AMBIENT_INTENSITY;
+        material
+    }
+}
+
+#[derive(Copy, Clone)]
+enum SurfaceMaterial<'a> {
+    Phong(Material<'a>),
+    Pbr(PbrMaterial<'a>),
+}
+
+impl SurfaceMaterial<'_> {
+    #[allow(clippy::too_many_arguments)]
+    fn shade(
+        &self,
+        uv: &Vec2,
+        position: &Vec3,
+        normal: &Vec3,
+        eye: &Vec3,
+        lights: &[Light],
+        ambient_visibility: f32,
+        light_visibility: impl Fn(&Light) -> f32,
+    ) -> Colorf {
+        match self {
+            SurfaceMaterial::Phong(material) => material.shade(
+                uv,
+                position,
+                normal,
+                eye,
+                lights,
+                ambient_visibility,
+                light_visibility,
+            ),
+            SurfaceMaterial::Pbr(material) => material.shade(
+                uv,
+                position,
+                normal,
+                eye,
+                lights,
+                ambient_visibility,
+                light_visibility,
+            ),
+        }
+    }
+}
+
+struct DepthShader<'a> {
+    model: &'a Model,
+    mvp: Mat4,
+}
+
+impl Shader for DepthShader<'_> {
+    type Varying = ();
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        (self.mvp * self.model.verts[face * 3 + vert].push(1f32), ())
+    }
+
+    fn fragment(&self, _varying: &Self::Varying) -> Option<Colorf> {
+        Some(Colorf::zeros())
+    }
+}
+
+struct PhongShader<'a> {
+    model: &'a Model,
+    material: SurfaceMaterial<'a>,
+    normal_map: NormalMap<'a>,
+    mvp: Mat4,
+    eye: Vec3,
+    // Directional lights are shadowed by `shadow_map`.
+    lights: &'a [Light],
+    shadow_map: &'a ShadowMap,
+    // Ambient occlusion of the ambient term, none when SSAO is disabled.
+    ssao: Option<&'a Ssao>,
+}
+
+impl Shader for PhongShader<'_> {
+    type Varying = (Vec2, Vec3, Vec3, Vec3, Vec3);
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        let i = face * 3 + vert;
+        let pos = self.model.verts[i];
+        (
+            self.mvp * pos.push(1f32),
+            (
+                self.model.uvs[i],
+                self.model.norms[i],
+                self.model.tangents[i],
+                self.model.bitangents[i],
+                pos,
+            ),
+        )
+    }
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf> {
+        let (uv, norm, tangent, bitangent, pos) = varying;
+        let norm = self.normal_map.normal(uv, norm, tangent, bitangent);
+        let ambient_visibility = self.ssao.map_or(1f32, |ssao| ssao.visibility(pos));
+        let light_visibility = |light: &Light| match light {
+            Light::Directional { .. } => self.shadow_map.visibility(pos),
+            _ => 1f32,
+        };
+        Some(self.material.shade(
+            uv,
+            pos,
+            &norm,
+            &self.eye,
+            self.lights,
+            ambient_visibility,
+            light_visibility,
+        ))
+    }
+}
+
+fn draw_depth(framebuffer: &mut Framebuffer, camera: &Camera, scene: &[Mesh]) {
+    for mesh in scene {
+        let shader = DepthShader {
+            model: &mesh.model,
+            mvp: camera.view_projection_matrix(),
+        };
+        draw_triangles(framebuffer, &shader, mesh.model.verts.len() / 3);
+    }
+}
+
+fn draw(
+    framebuffer: &mut Framebuffer,
+    camera: &Camera,
+    scene: &[Mesh],
+    lights: &[Light],
+    shadow_map: &ShadowMap,
+    ssao: Option<&Ssao>,
+    pbr: bool,
+) {
+    for mesh in scene {
+        let shader = PhongShader {
+            model: &mesh.model,
+            material: if pbr {
+                SurfaceMaterial::Pbr(mesh.pbr_material())
+            } else {
+                SurfaceMaterial::Phong(mesh.material())
+            },
+            normal_map: NormalMap::new(&mesh.normal_map, NormalMapSpace::Tangent),
+            mvp: camera.view_projection_matrix(),
+            eye: camera.eye,
+            lights,
+            shadow_map,
+            ssao,
+        };
+        draw_triangles(framebuffer, &shader, mesh.model.verts.len() / 3);
     }
     // framebuffer.write("output.png").unwrap();
     // framebuffer.write_depth("output_depth.png").unwrap();
 }
 
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum AntiAliasing {
+    None,
+    Msaa,
+    Fxaa,
+}
+
+impl AntiAliasing {
+    fn next(self) -> Self {
+        match self {
+            AntiAliasing::None => AntiAliasing::Msaa,
+            AntiAliasing::Msaa => AntiAliasing::Fxaa,
+            AntiAliasing::Fxaa => AntiAliasing::None,
+        }
+    }
+}
+
+fn next_tone_map_operator(operator: ToneMapOperator) -> ToneMapOperator {
+    match operator {
+        ToneMapOperator::Clamp => ToneMapOperator::Reinhard,
+        ToneMapOperator::Reinhard => ToneMapOperator::ExtendedReinhard {
+            white_point: WHITE_POINT,
+        },
+        ToneMapOperator::ExtendedReinhard { .. } => ToneMapOperator::Aces,
+        ToneMapOperator::Aces => ToneMapOperator::Clamp,
+    }
+}
+
 fn rgba_to_bgra(dst: &mut [u32], src: &[u32]) {
     let src_u8 = unsafe { slice::from_raw_parts(src.as_ptr() as *const u8, src.len() * 4) };
     let dst_u8 = unsafe { slice::from_raw_parts_mut(dst.as_mut_ptr() as *mut u8, src.len() * 4) };
@@ -45,10 +238,48 @@ fn rgba_to_bgra(dst: &mut [u32], src: &[u32]) {
 
 const WIDTH: i32 = 1024;
 const HEIGHT: i32 = 1024;
+const CAMERA_DISTANCE: f32 = 3f32;
+const CAMERA_ROTATE_SPEED: f32 = 0.05f32;
+const MSAA_SAMPLES: i32 = 4;
+const SHADOW_MAP_SIZE: i32 = 2048;
+const LIGHT_DIR: Vec3 = Vec3::new(-1f32, -2f32, -1.5f32);
+const LIGHT_INTENSITY: f32 = 1.5f32;
+// Ambient light of the PBR preview, Phong materials keep their default flat ambient.
+const AMBIENT_INTENSITY: f32 = 0.2f32;
+const EXPOSURE_SPEED: f32 = 0.05f32;
+const WHITE_POINT: f32 = 4f32;
+// SSAO is computed at a lower resolution than the display to keep it interactive.
+const SSAO_SCALE: i32 = 2;
+const SSAO_KERNEL_SIZE: usize = 16;
+const PBR_ROUGHNESS: f32 = 0.6f32;
 
 fn main() {
-    let model = Model::load("assets/african_head/african_head.obj").unwrap();
-    let diffuse_texture = Texture2D::load("assets/african_head/african_head_diffuse.png").unwrap();
+    let scene = [
+        Mesh::load("assets/diablo3_pose", "diablo3_pose"),
+        Mesh::load("assets/floor", "floor"),
+    ];
+    // The scene and the light are static, so the shadow map is only rendered once.
+    let mut shadow_map = ShadowMap::create(SHADOW_MAP_SIZE).unwrap();
+    shadow_map.set_light(&LIGHT_DIR, &Vec3::zeros(), 3f32.sqrt());
+    let lights = [
+        Light::directional(LIGHT_DIR, Vec3::repeat(1f32), LIGHT_INTENSITY),
+        Light::point(
+            Vec3::new(1f32, 0.2f32, 1f32),
+            Vec3::new(1f32, 0.4f32, 0.1f32),
+            0.5f32,
+        ),
+        Light::spot(
+            Vec3::new(-1f32, 1.5f32, 1f32),
+            Vec3::new(1f32, -1.5f32, -1f32),
+            Vec3::new(0.2f32, 0.4f32, 1f32),
+            1f32,
+            0.2f32,
+            0.35f32,
+        ),
+    ];
+    for mesh in &scene {
+        shadow_map.draw(&mesh.model);
+    }
     let mut window = Window::new(
         "Tiny Renderer - ESC to exit",
         WIDTH as usize,
@@ -56,24 +287,128 @@ fn main() {
         WindowOptions::default(),
     )
     .unwrap();
+    let mut camera = Camera::perspective(
+        std::f32::consts::FRAC_PI_4,
+        WIDTH as f32 / HEIGHT as f32,
+        0.1f32,
+        100f32,
+    );
+    let mut yaw = 0f32;
+    let mut pitch = 0f32;
     let mut fps = Fps::default();
-    let mut framebuffer = Framebuffer::create_init_color(WIDTH, HEIGHT, &Color::black()).unwrap();
+    let mut anti_aliasing = AntiAliasing::Msaa;
+    let mut msaa_framebuffer =
+        Framebuffer::create_multisample_hdr(WIDTH, HEIGHT, MSAA_SAMPLES).unwrap();
+    let mut resolved_framebuffer = Framebuffer::create_hdr(WIDTH, HEIGHT).unwrap();
+    let mut display_framebuffer = Framebuffer::create(WIDTH, HEIGHT).unwrap();
+    // Lighting is computed on linear HDR values, tone mapped and encoded to sRGB for display.
+    display_framebuffer.set_color_space(ColorSpace::Srgb);
+    let mut post_process = PostProcess::new();
+    post_process.add_pass(Bloom::default());
+    // FXAA runs after tone mapping, on the colors that are displayed.
+    let mut fxaa = PostProcess::new();
+    fxaa.add_pass(Fxaa::default());
+    let mut tone_mapping = ToneMapping::new(ToneMapOperator::Aces);
+    let mut depth_framebuffer =
+        Framebuffer::create_depth_only(WIDTH / SSAO_SCALE, HEIGHT / SSAO_SCALE).unwrap();
+    let mut ssao = Ssao::new(SSAO_KERNEL_SIZE);
+    let mut ssao_enabled = true;
+    let mut pbr = false;
+    let rasterizer_state = RasterizerState {
+        cull_mode: CullMode::Back,
+        front_face: FrontFace::CounterClockwise,
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    msaa_framebuffer.set_rasterizer_state(&rasterizer_state);
+    resolved_framebuffer.set_rasterizer_state(&rasterizer_state);
+    depth_framebuffer.set_rasterizer_state(&rasterizer_state);
     let mut bgra_buffer: Vec<u32> = vec![0; (WIDTH * HEIGHT) as usize];
     while window.is_open() && !window.is_key_down(Key::Escape) {
+        if window.is_key_down(Key::Left) {
+            yaw -= CAMERA_ROTATE_SPEED;
+        }
+        if window.is_key_down(Key::Right) {
+            yaw += CAMERA_ROTATE_SPEED;
+        }
+        if window.is_key_down(Key::Up) {
+            pitch = (pitch + CAMERA_ROTATE_SPEED).min(1.5f32);
+        }
+        if window.is_key_down(Key::Down) {
+            pitch = (pitch - CAMERA_ROTATE_SPEED).max(-1.5f32);
+        }
+        if window.is_key_pressed(Key::T, KeyRepeat::No) {
+            tone_mapping.operator = next_tone_map_operator(tone_mapping.operator);
+        }
+        if window.is_key_pressed(Key::A, KeyRepeat::No) {
+            anti_aliasing = anti_aliasing.next();
+        }
+        if window.is_key_pressed(Key::O, KeyRepeat::No) {
+            ssao_enabled = !ssao_enabled;
+        }
+        if window.is_key_pressed(Key::P, KeyRepeat::No) {
+            pbr = !pbr;
+        }
+        if window.is_key_down(Key::Equal) {
+            tone_mapping.exposure += EXPOSURE_SPEED;
+        }
+        if window.is_key_down(Key::Minus) {
+            tone_mapping.exposure -= EXPOSURE_SPEED;
+        }
+        let eye = Vec3::new(
+            yaw.sin() * pitch.cos(),
+            pitch.sin(),
+            yaw.cos() * pitch.cos(),
+        ) * CAMERA_DISTANCE;
+        camera.look_at(&eye, &Vec3::zeros(), &Vec3::y());
+        if ssao_enabled {
+            depth_framebuffer.clear_depth();
+            draw_depth(&mut depth_framebuffer, &camera, &scene);
+            ssao.compute(&depth_framebuffer, &camera, None).unwrap();
+        }
+        let framebuffer = match anti_aliasing {
+            AntiAliasing::Msaa => &mut msaa_framebuffer,
+            _ => &mut resolved_framebuffer,
+        };
         framebuffer.clear_color_with(&Color::black());
         framebuffer.clear_depth();
-        draw(&mut framebuffer, &model, &diffuse_texture);
-        let rgba_buffer = framebuffer.to_u32_slice();
+        draw(
+            framebuffer,
+            &camera,
+            &scene,
+            &lights,
+            &shadow_map,
+            ssao_enabled.then_some(&ssao),
+            pbr,
+        );
+        if anti_aliasing == AntiAliasing::Msaa {
+            msaa_framebuffer
+                .resolve_to(&mut resolved_framebuffer)
+                .unwrap();
+        }
+        post_process.apply(&mut resolved_framebuffer).unwrap();
+        resolved_framebuffer
+            .tone_map_to(&mut display_framebuffer, &tone_mapping)
+            .unwrap();
+        if anti_aliasing == AntiAliasing::Fxaa {
+            fxaa.apply(&mut display_framebuffer).unwrap();
+        }
+        let rgba_buffer = display_framebuffer.to_u32_slice().unwrap();
         rgba_to_bgra(&mut bgra_buffer, rgba_buffer);
         window
             .update_with_buffer(
                 &bgra_buffer,
-                framebuffer.width as usize,
-                framebuffer.height as usize,
+                display_framebuffer.width as usize,
+                display_framebuffer.height as usize,
             )
             .unwrap();
         if let FpsRet::Update(fps) = fps.update() {
-            window.set_title(format!("Tiny Renderer - ESC to exit (FPS: {})", fps).as_str());
+            window.set_title(
+                format!(
+                    "Tiny Renderer - ESC to exit (FPS: {}, {:?}, SSAO: {}, PBR: {})",
+                    fps, anti_aliasing, ssao_enabled, pbr
+                )
+                .as_str(),
+            );
         }
     }
 }
@@ -0,0 +1,177 @@
+use crate::{Colorf, Light, Texture2D, Texture2DFilterMode, Texture2DWrapMode, Vec2, Vec3};
+
+// Specular exponent for a sample of a specular map, read from its red channel as in the
+// `*_spec.png` maps of the tinyrenderer course.
+pub(crate) fn specular_exponent(sample: &Colorf) -> f32 {
+    5f32 + sample.x * 255f32
+}
+
+// Samples a material texture at `uv`.
+pub(crate) fn sample_texture(texture: &Texture2D, uv: &Vec2) -> Colorf {
+    texture.texture(
+        uv.x,
+        uv.y,
+        Texture2DWrapMode::ClampToEdge,
+        Texture2DFilterMode::Linear,
+    )
+}
+
+// Fraction of the light arriving from `light_dir` that is reflected towards `view_dir`, before
+// tinting by the albedo. `specular` is the intensity and exponent of the highlight, which is only
+// added on the lit side of the surface.
+pub(crate) fn blinn_phong(
+    normal: &Vec3,
+    view_dir: &Vec3,
+    light_dir: &Vec3,
+    specular: Option<(f32, f32)>,
+) -> f32 {
+    let n_dot_l = normal.dot(light_dir);
+    if n_dot_l <= 0f32 {
+        return 0f32;
+    }
+    match specular {
+        Some((intensity, exponent)) => {
+            let half_dir = (light_dir + view_dir).normalize();
+            n_dot_l + intensity * normal.dot(&half_dir).max(0f32).powf(exponent)
+        }
+        None => n_dot_l,
+    }
+}
+
+// Blinn-Phong surface with diffuse, specular, ambient and emissive terms, combined as
+// `ambient + albedo * (diffuse + specular_intensity * specular) + glow` like the tinyrenderer
+// course, whose highlight uses the reflected light vector instead of the half vector.
+#[derive(Copy, Clone)]
+pub struct Material<'a> {
+    pub diffuse: &'a Texture2D,
+    pub specular: Option<&'a Texture2D>,
+    // Emitted light, added to the shaded color regardless of the lights.
+    pub glow: Option<&'a Texture2D>,
+    // Light added to every channel regardless of the albedo and the lights.
+    pub ambient: f32,
+    pub specular_intensity: f32,
+    pub glow_intensity: f32,
+}
+
+impl<'a> Material<'a> {
+    pub fn new(diffuse: &'a Texture2D) -> Self {
+        Material {
+            diffuse,
+            specular: None,
+            glow: None,
+            ambient: 5f32 / 255f32,
+            specular_intensity: 0.6f32,
+            glow_intensity: 1f32,
+        }
+    }
+
+    pub fn with_specular(self, specular: &'a Texture2D) -> Self {
+        Material {
+            specular: Some(specular),
+            ..self
+        }
+    }
+
+    pub fn with_glow(self, glow: &'a Texture2D) -> Self {
+        Material {
+            glow: Some(glow),
+            ..self
+        }
+    }
+
+    pub fn albedo(&self, uv: &Vec2) -> Colorf {
+        sample_texture(self.diffuse, uv)
+    }
+
+    pub fn emission(&self, uv: &Vec2) -> Vec3 {
+        match self.glow {
+            Some(glow) => sample_texture(glow, uv).xyz() * self.glow_intensity,
+            None => Vec3::zeros(),
+        }
+    }
+
+    // Light reflected towards `view_dir` from a single light arriving from `light_dir` with
+    // `radiance`, both directions point away from the surface and `normal` is a unit vector.
+    pub fn reflect(
+        &self,
+        uv: &Vec2,
+        normal: &Vec3,
+        view_dir: &Vec3,
+        light_dir: &Vec3,
+        radiance: &Vec3,
+    ) -> Vec3 {
+        let specular = self.specular.map(|specular| {
+            let exponent = specular_exponent(&sample_texture(specular, uv));
+            (self.specular_intensity, exponent)
+        });
+        let intensity = blinn_phong(normal, view_dir, light_dir, specular);
+        (self.albedo(uv).xyz() * intensity).component_mul(radiance)
+    }
+
+    // Shades the surface at `position` seen from `eye` and lit by `lights`. `ambient_visibility`
+    // scales the ambient term, e.g. with ambient occlusion, and `light_visibility` returns the
+    // fraction of a light that reaches `position`, e.g. from a shadow map.
+    #[allow(clippy::too_many_arguments)]
+    pub fn shade(
+        &self,
+        uv: &Vec2,
+        position: &Vec3,
+        normal: &Vec3,
+        eye: &Vec3,
+        lights: &[Light],
+        ambient_visibility: f32,
+        light_visibility: impl Fn(&Light) -> f32,
+    ) -> Colorf {
+        let albedo = self.albedo(uv);
+        let view_dir = (eye - position).normalize();
+        let mut color = Vec3::repeat(self.ambient * ambient_visibility) + self.emission(uv);
+        for light in lights {
+            let (light_dir, radiance) = light.incident(position);
+            let radiance = radiance * light_visibility(light);
+            color += self.reflect(uv, normal, &view_dir, &light_dir, &radiance);
+        }
+        color.push(albedo.w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_material() {
+        let diffuse = Texture2D::create_init_color(1, 1, &Color::new(255, 0, 0, 255)).unwrap();
+        let specular = Texture2D::create_init_color(1, 1, &Color::new(0, 0, 0, 255)).unwrap();
+        let glow = Texture2D::create_init_color(1, 1, &Color::new(0, 0, 255, 255)).unwrap();
+        let uv = Vec2::new(0.5f32, 0.5f32);
+        let normal = Vec3::z();
+        let eye = Vec3::new(0f32, 0f32, 2f32);
+        let lights = [Light::directional(-Vec3::z(), Vec3::repeat(1f32), 1f32)];
+
+        let ambient = 5f32 / 255f32;
+        let shade = |material: &Material, normal: &Vec3| {
+            material.shade(&uv, &Vec3::zeros(), normal, &eye, &lights, 1f32, |_| 1f32)
+        };
+        let material = Material::new(&diffuse);
+        let color = shade(&material, &normal);
+        assert_relative_eq!(color, Colorf::new(1f32 + ambient, ambient, ambient, 1f32));
+
+        // The highlight is tinted by the albedo like the diffuse term.
+        let material = material.with_specular(&specular).with_glow(&glow);
+        let color = shade(&material, &normal);
+        assert_relative_eq!(
+            color,
+            Colorf::new(1.6f32 + ambient, ambient, 1f32 + ambient, 1f32)
+        );
+
+        // Surfaces facing away from the light only keep the ambient and emissive terms.
+        let color = shade(&material, &-normal);
+        assert_relative_eq!(color, Colorf::new(ambient, ambient, 1f32 + ambient, 1f32));
+
+        // Occluded ambient light and shadowed lights only leave the emissive term.
+        let color = material.shade(&uv, &Vec3::zeros(), &normal, &eye, &lights, 0f32, |_| 0f32);
+        assert_relative_eq!(color, Colorf::new(0f32, 0f32, 1f32, 1f32));
+    }
+}
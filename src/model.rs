@@ -1,4 +1,5 @@
 use crate::{Vec2, Vec3};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufRead;
 use std::path::Path;
@@ -7,6 +8,9 @@ pub struct Model {
     pub verts: Vec<Vec3>,
     pub uvs: Vec<Vec2>,
     pub norms: Vec<Vec3>,
+    // Unit vectors along +u and +v of the texture coordinates, orthogonal to the normal.
+    pub tangents: Vec<Vec3>,
+    pub bitangents: Vec<Vec3>,
 }
 
 #[derive(Debug)]
@@ -37,6 +41,18 @@ impl From<std::num::ParseIntError> for ModelError {
 }
 
 impl Model {
+    // Builds a model from per-corner attributes, three corners per face.
+    pub fn new(verts: Vec<Vec3>, uvs: Vec<Vec2>, norms: Vec<Vec3>) -> Self {
+        let (tangents, bitangents) = Self::all_tangents(&verts, &uvs, &norms);
+        Model {
+            verts,
+            uvs,
+            norms,
+            tangents,
+            bitangents,
+        }
+    }
+
     pub fn load(filepath: impl AsRef<Path>) -> Result<Self, ModelError> {
         let file = File::open(filepath)?;
         let lines = std::io::BufReader::new(file).lines();
@@ -46,7 +62,7 @@ impl Model {
         let mut v_faces: Vec<Vec<usize>> = Vec::new();
         let mut vn_faces: Vec<Vec<usize>> = Vec::new();
         let mut vt_faces: Vec<Vec<usize>> = Vec::new();
-        for line in lines.flatten() {
+        for line in lines.map_while(Result::ok) {
             let line = line.trim();
             if line.is_empty() || line.starts_with('#') {
                 continue;
@@ -146,11 +162,11 @@ impl Model {
                 continue;
             }
         }
-        Ok(Model {
-            verts: Self::all_verts(&verts, &v_faces),
-            uvs: Self::all_uvs(&uvs, &vt_faces),
-            norms: Self::all_norms(&norms, &vn_faces),
-        })
+        Ok(Model::new(
+            Self::all_verts(&verts, &v_faces),
+            Self::all_uvs(&uvs, &vt_faces),
+            Self::all_norms(&norms, &vn_faces),
+        ))
     }
 
     fn vert<'a>(
@@ -209,4 +225,88 @@ impl Model {
         }
         data
     }
+
+    // Computes the tangent and bitangent of every face from its positions and texture coordinates,
+    // then averages them over the faces sharing a vertex with the same position, uv and normal.
+    fn all_tangents(verts: &[Vec3], uvs: &[Vec2], norms: &[Vec3]) -> (Vec<Vec3>, Vec<Vec3>) {
+        let key = |i: usize| {
+            [
+                verts[i].x, verts[i].y, verts[i].z, uvs[i].x, uvs[i].y, norms[i].x, norms[i].y,
+                norms[i].z,
+            ]
+            .map(f32::to_bits)
+        };
+        let mut sums: HashMap<[u32; 8], (Vec3, Vec3)> = HashMap::new();
+        for face in 0..verts.len() / 3 {
+            let i = face * 3;
+            let e1 = verts[i + 1] - verts[i];
+            let e2 = verts[i + 2] - verts[i];
+            let d1 = uvs[i + 1] - uvs[i];
+            let d2 = uvs[i + 2] - uvs[i];
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() < f32::EPSILON {
+                continue;
+            }
+            let tangent = (e1 * d2.y - e2 * d1.y) / det;
+            let bitangent = (e2 * d1.x - e1 * d2.x) / det;
+            for j in i..i + 3 {
+                let sum = sums.entry(key(j)).or_insert((Vec3::zeros(), Vec3::zeros()));
+                sum.0 += tangent;
+                sum.1 += bitangent;
+            }
+        }
+
+        let mut tangents: Vec<Vec3> = Vec::with_capacity(verts.len());
+        let mut bitangents: Vec<Vec3> = Vec::with_capacity(verts.len());
+        for (i, norm) in norms.iter().enumerate() {
+            let norm = norm.normalize();
+            let (tangent, bitangent) = sums
+                .get(&key(i))
+                .copied()
+                .unwrap_or((Vec3::zeros(), Vec3::zeros()));
+            // Gram-Schmidt, falling back to any vector orthogonal to the normal.
+            let mut tangent = tangent - norm * norm.dot(&tangent);
+            if tangent.norm() < f32::EPSILON {
+                let axis = if norm.x.abs() < 0.9f32 {
+                    Vec3::x()
+                } else {
+                    Vec3::y()
+                };
+                tangent = axis - norm * norm.dot(&axis);
+            }
+            let tangent = tangent.normalize();
+            let handedness = if norm.cross(&tangent).dot(&bitangent) < 0f32 {
+                -1f32
+            } else {
+                1f32
+            };
+            tangents.push(tangent);
+            bitangents.push(norm.cross(&tangent) * handedness);
+        }
+        (tangents, bitangents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_tangents() {
+        let model = Model::load("assets/floor/floor.obj").unwrap();
+        for i in 0..model.verts.len() {
+            assert_relative_eq!(model.tangents[i], Vec3::x());
+            assert_relative_eq!(model.bitangents[i], Vec3::z());
+        }
+
+        // Mirrored texture coordinates flip the bitangent.
+        let model = Model::new(
+            vec![Vec3::zeros(), Vec3::x(), Vec3::y()],
+            vec![Vec2::zeros(), Vec2::x(), -Vec2::y()],
+            vec![Vec3::z(); 3],
+        );
+        assert_relative_eq!(model.tangents[0], Vec3::x());
+        assert_relative_eq!(model.bitangents[0], -Vec3::y());
+    }
 }
@@ -0,0 +1,48 @@
+use crate::{Texture2D, Texture2DFilterMode, Texture2DWrapMode, Vec2, Vec3};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum NormalMapSpace {
+    // Normals relative to the interpolated tangent, bitangent and normal, as in `*_nm_tangent.png`.
+    Tangent,
+    // Normals in model space, as in `*_nm.png`.
+    Object,
+}
+
+// A texture storing unit normals remapped from [-1, 1] to [0, 1] in its rgb channels.
+#[derive(Copy, Clone)]
+pub struct NormalMap<'a> {
+    pub texture: &'a Texture2D,
+    pub space: NormalMapSpace,
+}
+
+impl<'a> NormalMap<'a> {
+    pub fn new(texture: &'a Texture2D, space: NormalMapSpace) -> Self {
+        NormalMap { texture, space }
+    }
+
+    // Returns the model space normal at `uv`, `norm`, `tangent` and `bitangent` are the
+    // interpolated vertex attributes and are only used by tangent space maps.
+    pub fn normal(&self, uv: &Vec2, norm: &Vec3, tangent: &Vec3, bitangent: &Vec3) -> Vec3 {
+        let color = self.texture.texture(
+            uv.x,
+            uv.y,
+            Texture2DWrapMode::Repeat,
+            Texture2DFilterMode::Linear,
+        );
+        let n = color.xyz() * 2f32 - Vec3::repeat(1f32);
+        match self.space {
+            NormalMapSpace::Tangent => {
+                // Interpolation denormalizes the basis, so it is orthonormalized again.
+                let norm = norm.normalize();
+                let t = (tangent - norm * norm.dot(tangent)).normalize();
+                let b = if norm.cross(&t).dot(bitangent) < 0f32 {
+                    -norm.cross(&t)
+                } else {
+                    norm.cross(&t)
+                };
+                (t * n.x + b * n.y + norm * n.z).normalize()
+            }
+            NormalMapSpace::Object => n.normalize(),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use crate::material::sample_texture;
+use crate::{Colorf, Light, Texture2D, Vec2, Vec3};
+use std::f32::consts::PI;
+
+// Reflectance at normal incidence of dielectrics.
+const DIELECTRIC_F0: f32 = 0.04f32;
+// Lower bound of the roughness, a perfectly smooth surface only reflects point lights at a
+// single point.
+const MIN_ROUGHNESS: f32 = 0.03f32;
+
+// GGX (Trowbridge-Reitz) normal distribution for `alpha` = roughness^2.
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let alpha_sq = alpha * alpha;
+    let denom = n_dot_h * n_dot_h * (alpha_sq - 1f32) + 1f32;
+    alpha_sq / (PI * denom * denom)
+}
+
+// Schlick-GGX approximation of the Smith geometry term, for both the light and view directions.
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = (roughness + 1f32) * (roughness + 1f32) / 8f32;
+    let g1 = |n_dot_x: f32| n_dot_x / (n_dot_x * (1f32 - k) + k);
+    g1(n_dot_v) * g1(n_dot_l)
+}
+
+fn fresnel_schlick(v_dot_h: f32, f0: &Vec3) -> Vec3 {
+    f0 + (Vec3::repeat(1f32) - f0) * (1f32 - v_dot_h).clamp(0f32, 1f32).powi(5)
+}
+
+// Metallic-roughness surface shaded with a Cook-Torrance BRDF, following the conventions of glTF
+// 2.0. Textures are multiplied by their factor, a missing texture only uses the factor. Base
+// color and emissive textures hold sRGB colors and should be loaded with `Texture2D::load_srgb`,
+// the other textures hold linear values.
+#[derive(Copy, Clone)]
+pub struct PbrMaterial<'a> {
+    pub base_color: Option<&'a Texture2D>,
+    pub base_color_factor: Colorf,
+    // Roughness in the green channel and metallic in the blue channel.
+    pub metallic_roughness: Option<&'a Texture2D>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    // Ambient occlusion in the red channel, only applied to the ambient term.
+    pub occlusion: Option<&'a Texture2D>,
+    pub occlusion_strength: f32,
+    pub emissive: Option<&'a Texture2D>,
+    pub emissive_factor: Vec3,
+    // Fraction of the base color that is lit without any light.
+    pub ambient: f32,
+}
+
+impl Default for PbrMaterial<'_> {
+    fn default() -> Self {
+        PbrMaterial {
+            base_color: None,
+            base_color_factor: Colorf::repeat(1f32),
+            metallic_roughness: None,
+            metallic_factor: 1f32,
+            roughness_factor: 1f32,
+            occlusion: None,
+            occlusion_strength: 1f32,
+            emissive: None,
+            emissive_factor: Vec3::zeros(),
+            ambient: 0.1f32,
+        }
+    }
+}
+
+impl<'a> PbrMaterial<'a> {
+    // A uniform material without textures.
+    pub fn new(base_color: &Colorf, metallic: f32, roughness: f32) -> Self {
+        PbrMaterial {
+            base_color_factor: *base_color,
+            metallic_factor: metallic,
+            roughness_factor: roughness,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_base_color(self, base_color: &'a Texture2D) -> Self {
+        PbrMaterial {
+            base_color: Some(base_color),
+            ..self
+        }
+    }
+
+    pub fn with_metallic_roughness(self, metallic_roughness: &'a Texture2D) -> Self {
+        PbrMaterial {
+            metallic_roughness: Some(metallic_roughness),
+            ..self
+        }
+    }
+
+    pub fn with_occlusion(self, occlusion: &'a Texture2D) -> Self {
+        PbrMaterial {
+            occlusion: Some(occlusion),
+            ..self
+        }
+    }
+
+    pub fn with_emissive(self, emissive: &'a Texture2D, factor: &Vec3) -> Self {
+        PbrMaterial {
+            emissive: Some(emissive),
+            emissive_factor: *factor,
+            ..self
+        }
+    }
+
+    pub fn base_color(&self, uv: &Vec2) -> Colorf {
+        match self.base_color {
+            Some(texture) => sample_texture(texture, uv).component_mul(&self.base_color_factor),
+            None => self.base_color_factor,
+        }
+    }
+
+    // Returns (metallic, roughness).
+    pub fn metallic_roughness(&self, uv: &Vec2) -> (f32, f32) {
+        let (metallic, roughness) = match self.metallic_roughness {
+            Some(texture) => {
+                let sample = sample_texture(texture, uv);
+                (sample.z, sample.y)
+            }
+            None => (1f32, 1f32),
+        };
+        (
+            (metallic * self.metallic_factor).clamp(0f32, 1f32),
+            (roughness * self.roughness_factor).clamp(MIN_ROUGHNESS, 1f32),
+        )
+    }
+
+    pub fn occlusion(&self, uv: &Vec2) -> f32 {
+        match self.occlusion {
+            Some(texture) => {
+                1f32 + self.occlusion_strength * (sample_texture(texture, uv).x - 1f32)
+            }
+            None => 1f32,
+        }
+    }
+
+    pub fn emission(&self, uv: &Vec2) -> Vec3 {
+        match self.emissive {
+            Some(texture) => sample_texture(texture, uv)
+                .xyz()
+                .component_mul(&self.emissive_factor),
+            None => self.emissive_factor,
+        }
+    }
+
+    // Light reflected towards `view_dir` from a single light arriving from `light_dir` with
+    // `radiance`, both directions point away from the surface and `normal` is a unit vector.
+    // The BRDF is scaled by pi so that a rough white dielectric lit by a light of intensity 1
+    // is as bright as the Lambert term of `Material`.
+    pub fn reflect(
+        &self,
+        uv: &Vec2,
+        normal: &Vec3,
+        view_dir: &Vec3,
+        light_dir: &Vec3,
+        radiance: &Vec3,
+    ) -> Vec3 {
+        let n_dot_l = normal.dot(light_dir);
+        if n_dot_l <= 0f32 {
+            return Vec3::zeros();
+        }
+        // Normals interpolated or read from normal maps may face away from the eye.
+        let n_dot_v = normal.dot(view_dir).max(1e-4f32);
+        let half_dir = (light_dir + view_dir).normalize();
+        let n_dot_h = normal.dot(&half_dir).max(0f32);
+        let v_dot_h = view_dir.dot(&half_dir).max(0f32);
+
+        let base_color = self.base_color(uv).xyz();
+        let (metallic, roughness) = self.metallic_roughness(uv);
+        let f0 = Vec3::repeat(DIELECTRIC_F0).lerp(&base_color, metallic);
+        let fresnel = fresnel_schlick(v_dot_h, &f0);
+        let specular = fresnel
+            * (distribution_ggx(n_dot_h, roughness * roughness)
+                * geometry_smith(n_dot_v, n_dot_l, roughness)
+                / (4f32 * n_dot_v * n_dot_l));
+        // Metals have no diffuse term, and light reflected at the surface is not diffused.
+        let diffuse =
+            (Vec3::repeat(1f32) - fresnel).component_mul(&base_color) * (1f32 - metallic) / PI;
+        ((diffuse + specular) * PI * n_dot_l).component_mul(radiance)
+    }
+
+    // Shades the surface at `position` seen from `eye` and lit by `lights`, see `Material::shade`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn shade(
+        &self,
+        uv: &Vec2,
+        position: &Vec3,
+        normal: &Vec3,
+        eye: &Vec3,
+        lights: &[Light],
+        ambient_visibility: f32,
+        light_visibility: impl Fn(&Light) -> f32,
+    ) -> Colorf {
+        let base_color = self.base_color(uv);
+        let view_dir = (eye - position).normalize();
+        let ambient = self.ambient * self.occlusion(uv) * ambient_visibility;
+        let mut color = base_color.xyz() * ambient + self.emission(uv);
+        for light in lights {
+            let (light_dir, radiance) = light.incident(position);
+            let radiance = radiance * light_visibility(light);
+            color += self.reflect(uv, normal, &view_dir, &light_dir, &radiance);
+        }
+        color.push(base_color.w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Material};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_brdf() {
+        // The projected area of the microfacets covers the macro surface.
+        let steps = 2000;
+        for roughness in [0.2f32, 0.5f32, 1f32] {
+            let alpha = roughness * roughness;
+            let area: f32 = (0..steps)
+                .map(|i| {
+                    let theta = (i as f32 + 0.5f32) / steps as f32 * PI / 2f32;
+                    distribution_ggx(theta.cos(), alpha)
+                        * theta.cos()
+                        * theta.sin()
+                        * 2f32
+                        * PI
+                        * (PI / 2f32 / steps as f32)
+                })
+                .sum();
+            assert_relative_eq!(area, 1f32, epsilon = 1e-2);
+        }
+        assert_relative_eq!(geometry_smith(1f32, 1f32, 0.5f32), 1f32);
+        assert!(geometry_smith(0.1f32, 1f32, 0.5f32) < geometry_smith(0.5f32, 1f32, 0.5f32));
+        let f0 = Vec3::repeat(DIELECTRIC_F0);
+        assert_relative_eq!(fresnel_schlick(1f32, &f0), f0);
+        assert_relative_eq!(fresnel_schlick(0f32, &f0), Vec3::repeat(1f32));
+    }
+
+    #[test]
+    fn test_pbr_material() {
+        let uv = Vec2::new(0.5f32, 0.5f32);
+        let normal = Vec3::z();
+        let eye = Vec3::new(0f32, 0f32, 2f32);
+        let lights = [Light::directional(-Vec3::z(), Vec3::repeat(1f32), 1f32)];
+        let red = Colorf::new(1f32, 0f32, 0f32, 1f32);
+
+        // Without the specular peak, a rough dielectric is close to the Lambert term.
+        let diffuse = Texture2D::create_init_color(1, 1, &Color::new(255, 0, 0, 255)).unwrap();
+        let mut lambert = Material::new(&diffuse);
+        lambert.ambient = 0f32;
+        let mut dielectric = PbrMaterial::new(&red, 0f32, 1f32);
+        dielectric.ambient = 0f32;
+        let light_dir = Vec3::new(1f32, 0f32, 1f32).normalize();
+        let view_dir = Vec3::new(-1f32, 0f32, 1f32).normalize();
+        let radiance = Vec3::repeat(1f32);
+        let expected = lambert.reflect(&uv, &normal, &view_dir, &light_dir, &radiance);
+        let color = dielectric.reflect(&uv, &normal, &view_dir, &light_dir, &radiance);
+        assert_relative_eq!(color.x, expected.x, epsilon = 0.1f32);
+        assert!(color.y > 0f32 && color.y < 0.1f32);
+
+        // Metals tint their reflections and have no diffuse term, smooth surfaces have a
+        // brighter and narrower highlight.
+        let rough = PbrMaterial::new(&red, 1f32, 0.8f32);
+        let smooth = PbrMaterial::new(&red, 1f32, 0.2f32);
+        let highlight = rough.shade(&uv, &Vec3::zeros(), &normal, &eye, &lights, 1f32, |_| 1f32);
+        assert_relative_eq!(highlight.y, 0f32);
+        assert!(
+            smooth
+                .shade(&uv, &Vec3::zeros(), &normal, &eye, &lights, 1f32, |_| 1f32)
+                .x
+                > highlight.x
+        );
+        let off_peak = |material: &PbrMaterial| {
+            material
+                .reflect(&uv, &normal, &normal, &light_dir, &radiance)
+                .x
+        };
+        assert!(off_peak(&smooth) < off_peak(&rough));
+
+        // Textures are multiplied by their factors, occlusion only darkens the ambient term.
+        let white = Texture2D::create_init_color(1, 1, &Color::white()).unwrap();
+        let metallic_roughness =
+            Texture2D::create_init_color(1, 1, &Color::new(0, 128, 0, 255)).unwrap();
+        let occlusion = Texture2D::create_init_color(1, 1, &Color::new(0, 0, 0, 255)).unwrap();
+        let material = PbrMaterial::new(&red, 1f32, 1f32)
+            .with_base_color(&white)
+            .with_metallic_roughness(&metallic_roughness)
+            .with_occlusion(&occlusion)
+            .with_emissive(&white, &Vec3::new(0f32, 0f32, 2f32));
+        assert_relative_eq!(material.base_color(&uv), red);
+        let (metallic, roughness) = material.metallic_roughness(&uv);
+        assert_relative_eq!(metallic, 0f32);
+        assert_relative_eq!(roughness, 128f32 / 255f32);
+        assert_relative_eq!(material.occlusion(&uv), 0f32);
+        let unlit = material.shade(&uv, &Vec3::zeros(), &normal, &eye, &[], 1f32, |_| 1f32);
+        assert_relative_eq!(unlit, Colorf::new(0f32, 0f32, 2f32, 1f32));
+    }
+}
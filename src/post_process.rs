@@ -0,0 +1,61 @@
+use crate::{Framebuffer, FramebufferError};
+
+// A full-screen pass of a `PostProcess` pipeline.
+pub trait PostProcessPass {
+    // Reads `source` and writes the color of every pixel of `target`, which has the size, format
+    // and color space of `source`. The depth buffer and attachments of the scene are only
+    // available in `source`.
+    fn apply(&self, source: &Framebuffer, target: &mut Framebuffer)
+        -> Result<(), FramebufferError>;
+}
+
+// Ordered full-screen passes applied to the color buffer of a single-sampled framebuffer.
+#[derive(Default)]
+pub struct PostProcess {
+    passes: Vec<Box<dyn PostProcessPass>>,
+    target: Option<Framebuffer>,
+}
+
+impl PostProcess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_pass(&mut self, pass: impl PostProcessPass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    pub fn pass_count(&self) -> usize {
+        self.passes.len()
+    }
+
+    // Runs every pass in order, each one reading the output of the previous one, and leaves the
+    // result in the color buffer of `framebuffer`.
+    pub fn apply(&mut self, framebuffer: &mut Framebuffer) -> Result<(), FramebufferError> {
+        if framebuffer.samples() != 1 {
+            return Err(FramebufferError::BadSampleCount);
+        }
+        if framebuffer.attachment_format(0).is_none() {
+            return Err(FramebufferError::BadAttachment);
+        }
+        let matches = |target: &Framebuffer| {
+            target.width == framebuffer.width
+                && target.height == framebuffer.height
+                && target.is_hdr() == framebuffer.is_hdr()
+        };
+        if !self.target.as_ref().is_some_and(matches) {
+            self.target = Some(if framebuffer.is_hdr() {
+                Framebuffer::create_hdr(framebuffer.width, framebuffer.height)?
+            } else {
+                Framebuffer::create(framebuffer.width, framebuffer.height)?
+            });
+        }
+        let target = self.target.as_mut().unwrap();
+        target.set_color_space(framebuffer.color_space());
+        for pass in &self.passes {
+            pass.apply(framebuffer, target)?;
+            framebuffer.swap_color_buffers(target);
+        }
+        Ok(())
+    }
+}
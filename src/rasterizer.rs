@@ -0,0 +1,423 @@
+use crate::attachment::MAX_COLOR_ATTACHMENTS;
+use crate::clip::clip_triangle;
+use crate::framebuffer::FramebufferRows;
+use crate::{Colorf, Framebuffer, Shader, Varying, Vec2i, Vec3, Vec4};
+use std::cmp::{max, min};
+
+type Vec2l = nalgebra::Vector2<i64>;
+
+const SUBPIXEL_BITS: i64 = 8;
+const SUBPIXEL_ONE: i64 = 1 << SUBPIXEL_BITS;
+const SUBPIXEL_HALF: i64 = SUBPIXEL_ONE >> 1;
+
+// Size in pixels of the square blocks the rasterizer classifies before testing single pixels.
+const BLOCK_SIZE: i32 = 8;
+
+// Height in pixels of the full-width tiles used by the multithreaded back end.
+const TILE_SIZE: i32 = 32;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum CullMode {
+    #[default]
+    None,
+    Front,
+    Back,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum FrontFace {
+    #[default]
+    CounterClockwise,
+    Clockwise,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RasterizerState {
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    // Number of threads used by `draw_triangles`, values above 1 enable the tiled back end.
+    pub threads: usize,
+}
+
+impl Default for RasterizerState {
+    fn default() -> Self {
+        RasterizerState {
+            cull_mode: CullMode::default(),
+            front_face: FrontFace::default(),
+            threads: 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct RasterizerStats {
+    pub triangles: usize,
+    pub culled_triangles: usize,
+}
+
+impl RasterizerState {
+    // `signed_area` is twice the area of the triangle in window coordinates, where y points down,
+    // so a triangle that is counter-clockwise in NDC has a negative area.
+    pub fn is_front_facing(&self, signed_area: i64) -> bool {
+        let counter_clockwise = signed_area < 0;
+        counter_clockwise == (self.front_face == FrontFace::CounterClockwise)
+    }
+
+    pub fn is_culled(&self, signed_area: i64) -> bool {
+        let front_facing = self.is_front_facing(signed_area);
+        match self.cull_mode {
+            CullMode::None => false,
+            CullMode::Front => front_facing,
+            CullMode::Back => !front_facing,
+        }
+    }
+}
+
+// A clipped triangle in fixed-point window coordinates, ready to be rasterized.
+pub(crate) struct Triangle<V> {
+    p: [Vec2l; 3],
+    z: [f32; 3],
+    w_inv: Vec3,
+    varyings: [V; 3],
+    bias: [i64; 3],
+    area: i64,
+    front_facing: bool,
+    bounding_box_min: Vec2i,
+    bounding_box_max: Vec2i,
+}
+
+fn to_subpixel_pos(pos: &Vec3) -> Vec2l {
+    Vec2l::new(
+        (pos.x * SUBPIXEL_ONE as f32).round() as i64,
+        (pos.y * SUBPIXEL_ONE as f32).round() as i64,
+    )
+}
+
+fn edge_function(a: &Vec2l, b: &Vec2l, p: &Vec2l) -> i64 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// For a triangle with positive area in window coordinates (y pointing down), top edges run
+// towards +x and left edges run towards -y.
+fn is_top_left(a: &Vec2l, b: &Vec2l) -> bool {
+    (a.y == b.y && b.x > a.x) || b.y < a.y
+}
+
+// Clips the triangle, applies face culling and appends the resulting triangles to `triangles`.
+pub(crate) fn setup_triangle<V: Varying>(
+    framebuffer: &mut Framebuffer,
+    clip_coords: &[Vec4; 3],
+    varyings: &[V; 3],
+    triangles: &mut Vec<Triangle<V>>,
+) {
+    framebuffer.stats.triangles += 1;
+    let polygon = clip_triangle(clip_coords, varyings);
+    let mut culled = false;
+    for i in 2..polygon.len() {
+        let clip_coords = [polygon[0].0, polygon[i - 1].0, polygon[i].0];
+        let varyings = [polygon[0].1, polygon[i - 1].1, polygon[i].1];
+        match setup_clipped_triangle(framebuffer, &clip_coords, &varyings) {
+            Ok(Some(triangle)) => triangles.push(triangle),
+            Ok(None) => {}
+            Err(()) => culled = true,
+        }
+    }
+    if culled {
+        framebuffer.stats.culled_triangles += 1;
+    }
+}
+
+// Returns `Err` when the triangle is rejected by face culling and `None` when it covers no pixel.
+fn setup_clipped_triangle<V: Varying>(
+    framebuffer: &Framebuffer,
+    clip_coords: &[Vec4; 3],
+    varyings: &[V; 3],
+) -> Result<Option<Triangle<V>>, ()> {
+    let viewport = framebuffer.viewport();
+    let p0 = &viewport.to_window(&(clip_coords[0].xyz() / clip_coords[0].w));
+    let p1 = &viewport.to_window(&(clip_coords[1].xyz() / clip_coords[1].w));
+    let p2 = &viewport.to_window(&(clip_coords[2].xyz() / clip_coords[2].w));
+    let mut p = [
+        to_subpixel_pos(p0),
+        to_subpixel_pos(p1),
+        to_subpixel_pos(p2),
+    ];
+    let mut z = [p0.z, p1.z, p2.z];
+    let mut w_inv = [
+        1f32 / clip_coords[0].w,
+        1f32 / clip_coords[1].w,
+        1f32 / clip_coords[2].w,
+    ];
+    let mut varyings = *varyings;
+    let mut area = edge_function(&p[0], &p[1], &p[2]);
+    if area == 0 {
+        return Ok(None);
+    }
+    if framebuffer.rasterizer_state().is_culled(area) {
+        return Err(());
+    }
+    let front_facing = framebuffer.rasterizer_state().is_front_facing(area);
+    if area < 0 {
+        p.swap(1, 2);
+        z.swap(1, 2);
+        w_inv.swap(1, 2);
+        varyings.swap(1, 2);
+        area = -area;
+    }
+    let bias = [
+        if is_top_left(&p[1], &p[2]) { 0 } else { -1 },
+        if is_top_left(&p[2], &p[0]) { 0 } else { -1 },
+        if is_top_left(&p[0], &p[1]) { 0 } else { -1 },
+    ];
+
+    let (bounds_min, bounds_max) = framebuffer.draw_bounds();
+    let bounding_box_min = Vec2i::new(
+        max(
+            bounds_min.x,
+            (p[0].x.min(p[1].x).min(p[2].x) >> SUBPIXEL_BITS) as i32,
+        ),
+        max(
+            bounds_min.y,
+            (p[0].y.min(p[1].y).min(p[2].y) >> SUBPIXEL_BITS) as i32,
+        ),
+    );
+    let bounding_box_max = Vec2i::new(
+        min(
+            bounds_max.x,
+            (p[0].x.max(p[1].x).max(p[2].x) >> SUBPIXEL_BITS) as i32,
+        ),
+        min(
+            bounds_max.y,
+            (p[0].y.max(p[1].y).max(p[2].y) >> SUBPIXEL_BITS) as i32,
+        ),
+    );
+    if bounding_box_min.x > bounding_box_max.x || bounding_box_min.y > bounding_box_max.y {
+        return Ok(None);
+    }
+
+    Ok(Some(Triangle {
+        p,
+        z,
+        w_inv: Vec3::new(w_inv[0], w_inv[1], w_inv[2]),
+        varyings,
+        bias,
+        area,
+        front_facing,
+        bounding_box_min,
+        bounding_box_max,
+    }))
+}
+
+// Shades the fragment once at the pixel centre and writes it to every sample in `coverage`,
+// with the depth interpolated at that sample.
+#[allow(clippy::too_many_arguments)]
+fn shade_fragment<S: Shader, const SAMPLES: usize>(
+    rows: &mut FramebufferRows,
+    shader: &S,
+    triangle: &Triangle<S::Varying>,
+    x: i32,
+    y: i32,
+    w: &[i64; 3],
+    coverage: u32,
+    sample_deltas: &[[i64; 3]; SAMPLES],
+) {
+    let varyings = &triangle.varyings;
+    let bc_screen = Vec3::new(w[0] as f32, w[1] as f32, w[2] as f32) / triangle.area as f32;
+    let bc_clip: Vec3 = bc_screen.component_mul(&triangle.w_inv);
+    let bc_clip: Vec3 = bc_clip / (bc_clip.x + bc_clip.y + bc_clip.z);
+    let varying = S::Varying::interpolate(&varyings[0], &varyings[1], &varyings[2], &bc_clip);
+
+    let color;
+    let mut targets;
+    let colors: &[Option<Colorf>] = if rows.attachment_count() == 1 {
+        color = match shader.fragment(&varying) {
+            Some(color) => Some(color),
+            None => return,
+        };
+        std::slice::from_ref(&color)
+    } else {
+        targets = [None; MAX_COLOR_ATTACHMENTS];
+        let targets = &mut targets[..rows.attachment_count()];
+        if !shader.fragment_targets(&varying, targets) {
+            return;
+        }
+        targets
+    };
+    let z = &triangle.z;
+    for (sample, delta) in sample_deltas.iter().enumerate() {
+        if coverage & (1 << sample) == 0 {
+            continue;
+        }
+        let bc_screen = if *delta == [0i64; 3] {
+            bc_screen
+        } else {
+            Vec3::new(
+                (w[0] + delta[0]) as f32,
+                (w[1] + delta[1]) as f32,
+                (w[2] + delta[2]) as f32,
+            ) / triangle.area as f32
+        };
+        let depth = z[0] * bc_screen[0] + z[1] * bc_screen[1] + z[2] * bc_screen[2];
+        rows.set_sample_color_with_depth(x, y, sample, depth, triangle.front_facing, colors);
+    }
+}
+
+pub(crate) fn rasterize_triangle<S: Shader>(
+    rows: &mut FramebufferRows,
+    shader: &S,
+    triangle: &Triangle<S::Varying>,
+) {
+    match rows.sample_pattern.len() {
+        1 => rasterize_samples::<S, 1>(rows, shader, triangle),
+        2 => rasterize_samples::<S, 2>(rows, shader, triangle),
+        4 => rasterize_samples::<S, 4>(rows, shader, triangle),
+        _ => rasterize_samples::<S, 8>(rows, shader, triangle),
+    }
+}
+
+// Walks the bounding box in blocks of `BLOCK_SIZE` pixels. The edge functions are evaluated once
+// per block and then stepped incrementally, blocks entirely outside one edge are skipped and
+// blocks entirely inside all edges are filled without per-pixel coverage tests.
+fn rasterize_samples<S: Shader, const SAMPLES: usize>(
+    rows: &mut FramebufferRows,
+    shader: &S,
+    triangle: &Triangle<S::Varying>,
+) {
+    let p = &triangle.p;
+    let bias = &triangle.bias;
+    let x_begin = triangle.bounding_box_min.x;
+    let x_end = triangle.bounding_box_max.x + 1;
+    let y_begin = max(triangle.bounding_box_min.y, rows.y_begin);
+    let y_end = min(triangle.bounding_box_max.y + 1, rows.y_end);
+    if y_begin >= y_end {
+        return;
+    }
+
+    let edges = [(&p[1], &p[2]), (&p[2], &p[0]), (&p[0], &p[1])];
+    let step_x = edges.map(|(a, b)| (a.y - b.y) * SUBPIXEL_ONE);
+    let step_y = edges.map(|(a, b)| (b.x - a.x) * SUBPIXEL_ONE);
+    let origin = Vec2l::new(
+        x_begin as i64 * SUBPIXEL_ONE + SUBPIXEL_HALF,
+        y_begin as i64 * SUBPIXEL_ONE + SUBPIXEL_HALF,
+    );
+    let w_origin = edges.map(|(a, b)| edge_function(a, b, &origin));
+
+    // Edge function offsets from the pixel centre to each sample position.
+    let mut sample_deltas = [[0i64; 3]; SAMPLES];
+    for (delta, (dx, dy)) in sample_deltas.iter_mut().zip(rows.sample_pattern.iter()) {
+        for i in 0..3 {
+            delta[i] = (*dx as i64 * step_x[i] + *dy as i64 * step_y[i]) / 16;
+        }
+    }
+    let full_coverage = (1u32 << SAMPLES) - 1;
+    let delta_min = [0, 1, 2].map(|i| sample_deltas.iter().map(|d| d[i]).min().unwrap());
+    let delta_max = [0, 1, 2].map(|i| sample_deltas.iter().map(|d| d[i]).max().unwrap());
+
+    for block_y in (y_begin..y_end).step_by(BLOCK_SIZE as usize) {
+        let block_y_end = min(block_y + BLOCK_SIZE, y_end);
+        for block_x in (x_begin..x_end).step_by(BLOCK_SIZE as usize) {
+            let block_x_end = min(block_x + BLOCK_SIZE, x_end);
+            let dx = (block_x_end - block_x - 1) as i64;
+            let dy = (block_y_end - block_y - 1) as i64;
+            let mut w_block = [0i64; 3];
+            let mut rejected = false;
+            let mut accepted = true;
+            for i in 0..3 {
+                w_block[i] = w_origin[i]
+                    + (block_x - x_begin) as i64 * step_x[i]
+                    + (block_y - y_begin) as i64 * step_y[i];
+                let corners = [
+                    w_block[i],
+                    w_block[i] + dx * step_x[i],
+                    w_block[i] + dy * step_y[i],
+                    w_block[i] + dx * step_x[i] + dy * step_y[i],
+                ];
+                let corner_min = corners.iter().min().unwrap() + bias[i] + delta_min[i];
+                let corner_max = corners.iter().max().unwrap() + bias[i] + delta_max[i];
+                rejected |= corner_max < 0;
+                accepted &= corner_min >= 0;
+            }
+            if rejected {
+                continue;
+            }
+
+            let mut w_row = w_block;
+            for y in block_y..block_y_end {
+                let mut w = w_row;
+                for x in block_x..block_x_end {
+                    let coverage = if accepted {
+                        full_coverage
+                    } else {
+                        let mut coverage = 0u32;
+                        for (sample, delta) in sample_deltas.iter().enumerate() {
+                            if w[0] + delta[0] + bias[0] >= 0
+                                && w[1] + delta[1] + bias[1] >= 0
+                                && w[2] + delta[2] + bias[2] >= 0
+                            {
+                                coverage |= 1 << sample;
+                            }
+                        }
+                        coverage
+                    };
+                    if coverage != 0 {
+                        shade_fragment(rows, shader, triangle, x, y, &w, coverage, &sample_deltas);
+                    }
+                    for i in 0..3 {
+                        w[i] += step_x[i];
+                    }
+                }
+                for i in 0..3 {
+                    w_row[i] += step_y[i];
+                }
+            }
+        }
+    }
+}
+
+// Bins the triangles into tiles of `TILE_SIZE` rows and rasterizes the tiles in parallel. Each
+// tile owns a disjoint slice of the framebuffer and draws its triangles in submission order, so
+// the result is identical to rasterizing serially.
+pub(crate) fn rasterize_tiled<S: Shader>(
+    framebuffer: &mut Framebuffer,
+    shader: &S,
+    triangles: &[Triangle<S::Varying>],
+    threads: usize,
+) {
+    if triangles.is_empty() || framebuffer.width == 0 || framebuffer.height == 0 {
+        return;
+    }
+    let tile_count = ((framebuffer.height + TILE_SIZE - 1) / TILE_SIZE) as usize;
+    let mut bins: Vec<Vec<usize>> = vec![Vec::new(); tile_count];
+    for (i, triangle) in triangles.iter().enumerate() {
+        let tile_begin = (triangle.bounding_box_min.y / TILE_SIZE) as usize;
+        let tile_end = (triangle.bounding_box_max.y / TILE_SIZE) as usize;
+        for bin in bins[tile_begin..=tile_end].iter_mut() {
+            bin.push(i);
+        }
+    }
+
+    let mut work: Vec<Vec<(FramebufferRows, &[usize])>> =
+        (0..threads).map(|_| Vec::new()).collect();
+    for (i, (tile, bin)) in framebuffer
+        .rows_chunks_mut(TILE_SIZE)
+        .into_iter()
+        .zip(bins.iter())
+        .enumerate()
+    {
+        if !bin.is_empty() {
+            work[i % threads].push((tile, bin));
+        }
+    }
+    std::thread::scope(|scope| {
+        // Threads without a tile to draw are not started.
+        for tiles in work.into_iter().filter(|tiles| !tiles.is_empty()) {
+            scope.spawn(move || {
+                for (mut tile, bin) in tiles {
+                    for &i in bin {
+                        rasterize_triangle(&mut tile, shader, &triangles[i]);
+                    }
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,90 @@
+use crate::{Colorf, Vec2, Vec3, Vec4};
+
+pub trait Varying: Copy + Send + Sync {
+    fn interpolate(v0: &Self, v1: &Self, v2: &Self, bc: &Vec3) -> Self;
+
+    fn lerp(v0: &Self, v1: &Self, t: f32) -> Self {
+        Self::interpolate(v0, v1, v1, &Vec3::new(1f32 - t, t, 0f32))
+    }
+}
+
+impl Varying for () {
+    fn interpolate(_v0: &Self, _v1: &Self, _v2: &Self, _bc: &Vec3) -> Self {}
+}
+
+macro_rules! impl_varying_linear {
+    ($($t:ty),*) => {
+        $(
+            impl Varying for $t {
+                fn interpolate(v0: &Self, v1: &Self, v2: &Self, bc: &Vec3) -> Self {
+                    *v0 * bc.x + *v1 * bc.y + *v2 * bc.z
+                }
+            }
+        )*
+    };
+}
+
+impl_varying_linear!(f32, Vec2, Vec3, Vec4);
+
+macro_rules! impl_varying_tuple {
+    ($($name:ident $idx:tt),*) => {
+        impl<$($name: Varying),*> Varying for ($($name,)*) {
+            fn interpolate(v0: &Self, v1: &Self, v2: &Self, bc: &Vec3) -> Self {
+                ($($name::interpolate(&v0.$idx, &v1.$idx, &v2.$idx, bc),)*)
+            }
+        }
+    };
+}
+
+impl_varying_tuple!(A 0);
+impl_varying_tuple!(A 0, B 1);
+impl_varying_tuple!(A 0, B 1, C 2);
+impl_varying_tuple!(A 0, B 1, C 2, D 3);
+impl_varying_tuple!(A 0, B 1, C 2, D 3, E 4);
+
+/// A programmable pipeline stage pair driven by `draw_triangle` and `draw_triangles`.
+///
+/// `vertex` returns the clip-space position of the `vert`-th corner of the `face`-th triangle
+/// together with the varyings to interpolate across it. `fragment` turns interpolated varyings
+/// into a color, or returns `None` to discard the fragment.
+///
+/// Framebuffers with several color attachments call `fragment_targets` instead, which sets
+/// `targets[i]` for attachment `i` and returns `false` to discard the fragment. Attachments whose
+/// target is left `None` are not written. By default it only writes the result of `fragment` to
+/// the color buffer.
+pub trait Shader: Sync {
+    type Varying: Varying;
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying);
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf>;
+
+    fn fragment_targets(&self, varying: &Self::Varying, targets: &mut [Option<Colorf>]) -> bool {
+        match self.fragment(varying) {
+            Some(color) => {
+                targets[0] = Some(color);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_interpolate() {
+        let bc = Vec3::new(0.5f32, 0.25f32, 0.25f32);
+        let v = <(f32, Vec2)>::interpolate(
+            &(0f32, Vec2::new(0f32, 0f32)),
+            &(4f32, Vec2::new(1f32, 0f32)),
+            &(8f32, Vec2::new(0f32, 1f32)),
+            &bc,
+        );
+        assert_relative_eq!(v.0, 3f32);
+        assert_relative_eq!(v.1, Vec2::new(0.25f32, 0.25f32));
+    }
+}
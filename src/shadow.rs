@@ -0,0 +1,118 @@
+use crate::{
+    draw_triangles, Camera, Colorf, Framebuffer, FramebufferError, Mat4, Model, Shader, Vec3, Vec4,
+};
+
+struct ShadowShader<'a> {
+    model: &'a Model,
+    mvp: Mat4,
+}
+
+impl Shader for ShadowShader<'_> {
+    type Varying = ();
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        (self.mvp * self.model.verts[face * 3 + vert].push(1f32), ())
+    }
+
+    fn fragment(&self, _varying: &Self::Varying) -> Option<Colorf> {
+        Some(Colorf::zeros())
+    }
+}
+
+// Depth of the scene seen from a directional light through an orthographic projection.
+pub struct ShadowMap {
+    framebuffer: Framebuffer,
+    camera: Camera,
+    view_projection: Mat4,
+    // Added to the depth of a receiver before it is compared with the shadow map, in window depth
+    // units, to keep lit surfaces from shadowing themselves.
+    pub depth_bias: f32,
+    // Radius in texels of the square percentage-closer filtering kernel, which is 2 * radius + 1
+    // texels wide, 0 disables filtering.
+    pub pcf_radius: i32,
+}
+
+impl ShadowMap {
+    pub fn create(size: i32) -> Result<Self, FramebufferError> {
+        let mut shadow_map = ShadowMap {
+            framebuffer: Framebuffer::create_depth_only(size, size)?,
+            camera: Camera::orthographic(-1f32, 1f32, -1f32, 1f32, 1f32, 3f32),
+            view_projection: Mat4::identity(),
+            depth_bias: 0.005f32,
+            pcf_radius: 1,
+        };
+        shadow_map.set_light(&Vec3::new(0f32, -1f32, 0f32), &Vec3::zeros(), 1f32);
+        Ok(shadow_map)
+    }
+
+    // Fits the light view around the sphere of `radius` at `center`, `direction` points from the
+    // light towards the scene.
+    pub fn set_light(&mut self, direction: &Vec3, center: &Vec3, radius: f32) {
+        let direction = direction.normalize();
+        let up = if direction.cross(&Vec3::y()).norm() < 1e-3f32 {
+            Vec3::z()
+        } else {
+            Vec3::y()
+        };
+        self.camera = Camera::orthographic(-radius, radius, -radius, radius, radius, 3f32 * radius);
+        self.camera
+            .look_at(&(center - direction * 2f32 * radius), center, &up);
+        self.view_projection = self.camera.view_projection_matrix();
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    pub fn clear(&mut self) {
+        self.framebuffer.clear_depth();
+    }
+
+    // Renders `model` as a shadow caster.
+    pub fn draw(&mut self, model: &Model) {
+        let shader = ShadowShader {
+            model,
+            mvp: self.view_projection,
+        };
+        draw_triangles(&mut self.framebuffer, &shader, model.verts.len() / 3);
+    }
+
+    // Returns the lit fraction of the filtering kernel around `position`, from 0 when it is fully
+    // shadowed to 1. Positions outside of the shadow map are lit.
+    pub fn visibility(&self, position: &Vec3) -> f32 {
+        let clip = self.view_projection * position.push(1f32);
+        let window = self
+            .framebuffer
+            .viewport()
+            .to_window(&(clip.xyz() / clip.w));
+        let x = window.x.floor() as i32;
+        let y = window.y.floor() as i32;
+        let width = self.framebuffer.width;
+        let height = self.framebuffer.height;
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return 1f32;
+        }
+        let depth = window.z + self.depth_bias;
+        let radius = self.pcf_radius.max(0);
+        let mut lit = 0;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let stored = self
+                    .framebuffer
+                    .get_depth((x + dx).clamp(0, width - 1), (y + dy).clamp(0, height - 1));
+                if depth >= stored {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f32 / ((2 * radius + 1) * (2 * radius + 1)) as f32
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{Camera, Framebuffer, FramebufferError, Mat4, Vec3, Viewport};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// The kernel and the noise are generated from a fixed seed so that renders are reproducible.
+const SSAO_SEED: u64 = 0x55a0;
+// Width of the square tile of random kernel rotations, which the blur averages away.
+const NOISE_SIZE: i32 = 4;
+
+// Screen-space ambient occlusion, the fraction of a hemisphere around the surface of every pixel
+// that is not hidden by the depth buffer.
+pub struct Ssao {
+    // Radius of the hemisphere in view space units.
+    pub radius: f32,
+    // Depth difference in view space units below which a sample is not occluded, to avoid
+    // self-occlusion on flat surfaces.
+    pub bias: f32,
+    // Value the depth buffer was cleared to, pixels still at this depth were never drawn. Only
+    // used without a normal attachment.
+    pub clear_depth: f32,
+    kernel: Vec<Vec3>,
+    noise: Vec<Vec3>,
+    visibility: Vec<f32>,
+    view_projection: Mat4,
+    viewport: Viewport,
+    width: i32,
+    height: i32,
+}
+
+impl Ssao {
+    pub fn new(kernel_size: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(SSAO_SEED);
+        // Samples in the unit hemisphere around +z, more of them close to the center.
+        let kernel = (0..kernel_size)
+            .map(|i| {
+                let sample = Vec3::new(
+                    rng.gen::<f32>() * 2f32 - 1f32,
+                    rng.gen::<f32>() * 2f32 - 1f32,
+                    rng.gen::<f32>(),
+                )
+                .normalize()
+                    * rng.gen::<f32>();
+                let scale = i as f32 / kernel_size as f32;
+                sample * (0.1f32 + 0.9f32 * scale * scale)
+            })
+            .collect();
+        let noise = (0..NOISE_SIZE * NOISE_SIZE)
+            .map(|_| {
+                Vec3::new(
+                    rng.gen::<f32>() * 2f32 - 1f32,
+                    rng.gen::<f32>() * 2f32 - 1f32,
+                    0f32,
+                )
+            })
+            .collect();
+        Ssao {
+            radius: 0.1f32,
+            bias: 0.005f32,
+            clear_depth: f32::MIN,
+            kernel,
+            noise,
+            visibility: Vec::new(),
+            view_projection: Mat4::identity(),
+            viewport: Viewport::default(),
+            width: 0,
+            height: 0,
+        }
+    }
+
+    pub fn kernel_size(&self) -> usize {
+        self.kernel.len()
+    }
+
+    // Computes the occlusion of the depth buffer of `framebuffer` rendered with `camera`. Normals
+    // are read from `normal_attachment` when it is given, which stores world space normals with
+    // w = 1 for covered pixels like `GBUFFER_NORMAL`, otherwise they are reconstructed from the
+    // depth buffer and pixels at `clear_depth` are uncovered. Pixels that were never drawn are not
+    // occluded, and neither is anything with an empty kernel.
+    pub fn compute(
+        &mut self,
+        framebuffer: &Framebuffer,
+        camera: &Camera,
+        normal_attachment: Option<usize>,
+    ) -> Result<(), FramebufferError> {
+        let (width, height) = (framebuffer.width, framebuffer.height);
+        let viewport = *framebuffer.viewport();
+        let projection = camera.projection_matrix();
+        let inv_projection = projection
+            .try_inverse()
+            .filter(|m| m.iter().all(|v| v.is_finite()))
+            .ok_or(FramebufferError::BadCamera)?;
+        let view = camera.view_matrix();
+        let clear_depth = self.clear_depth;
+        let covered = |x: i32, y: i32| {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                return false;
+            }
+            match normal_attachment {
+                Some(index) => framebuffer
+                    .get_attachment(index, x, y)
+                    .is_ok_and(|normal| normal.w != 0f32),
+                None => framebuffer.get_depth(x, y) != clear_depth,
+            }
+        };
+        let position = |x: i32, y: i32| {
+            let window = Vec3::new(
+                x as f32 + 0.5f32,
+                y as f32 + 0.5f32,
+                framebuffer.get_depth(x, y),
+            );
+            let view_pos = inv_projection * viewport.to_ndc(&window).push(1f32);
+            view_pos.xyz() / view_pos.w
+        };
+        let mut occlusion = vec![1f32; (width * height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                if !covered(x, y) {
+                    continue;
+                }
+                let pos = position(x, y);
+                let normal = match normal_attachment {
+                    Some(index) => {
+                        let normal = framebuffer.get_attachment(index, x, y)?;
+                        (view * normal.xyz().push(0f32)).xyz().normalize()
+                    }
+                    None => {
+                        // One-sided differences towards the neighbors closest in depth, to
+                        // stay on the same surface at silhouettes.
+                        let difference = |dx: i32, dy: i32| {
+                            let forward =
+                                covered(x + dx, y + dy).then(|| position(x + dx, y + dy) - pos);
+                            let backward =
+                                covered(x - dx, y - dy).then(|| pos - position(x - dx, y - dy));
+                            match (forward, backward) {
+                                (Some(f), Some(b)) if b.z.abs() < f.z.abs() => Some(b),
+                                (f, b) => f.or(b),
+                            }
+                        };
+                        let (Some(ddx), Some(ddy)) = (difference(1, 0), difference(0, 1)) else {
+                            continue;
+                        };
+                        let normal = ddy.cross(&ddx);
+                        if normal.dot(&-pos) < 0f32 {
+                            -normal.normalize()
+                        } else {
+                            normal.normalize()
+                        }
+                    }
+                };
+                let noise_index = (y % NOISE_SIZE) * NOISE_SIZE + x % NOISE_SIZE;
+                let random = self.noise[noise_index as usize];
+                let tangent = (random - normal * random.dot(&normal)).normalize();
+                let bitangent = normal.cross(&tangent);
+                let mut occluded = 0f32;
+                for sample in &self.kernel {
+                    let sample = pos
+                        + (tangent * sample.x + bitangent * sample.y + normal * sample.z)
+                            * self.radius;
+                    let clip = projection * sample.push(1f32);
+                    let window = viewport.to_window(&(clip.xyz() / clip.w));
+                    let (sx, sy) = (window.x.floor() as i32, window.y.floor() as i32);
+                    if !covered(sx, sy) {
+                        continue;
+                    }
+                    // View space looks towards -z, the scene hides the sample when it is closer.
+                    let scene_z = position(sx, sy).z;
+                    if scene_z >= sample.z + self.bias {
+                        let range = (self.radius / (pos.z - scene_z).abs()).min(1f32);
+                        occluded += range * range * (3f32 - 2f32 * range);
+                    }
+                }
+                occlusion[(y * width + x) as usize] =
+                    1f32 - occluded / self.kernel.len().max(1) as f32;
+            }
+        }
+
+        // Box blur over the noise tile, only averaging covered pixels.
+        self.visibility = vec![1f32; (width * height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                if !covered(x, y) {
+                    continue;
+                }
+                let mut sum = 0f32;
+                let mut count = 0;
+                for dy in -NOISE_SIZE / 2..NOISE_SIZE / 2 {
+                    for dx in -NOISE_SIZE / 2..NOISE_SIZE / 2 {
+                        if covered(x + dx, y + dy) {
+                            sum += occlusion[((y + dy) * width + x + dx) as usize];
+                            count += 1;
+                        }
+                    }
+                }
+                self.visibility[(y * width + x) as usize] = sum / count as f32;
+            }
+        }
+        self.view_projection = camera.view_projection_matrix();
+        self.viewport = viewport;
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
+
+    // Returns the computed ambient visibility of a pixel, from 0 when it is fully occluded to 1.
+    pub fn get(&self, x: i32, y: i32) -> f32 {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return 1f32;
+        }
+        self.visibility[(y * self.width + x) as usize]
+    }
+
+    // Returns the ambient visibility of the pixel that the world space `position` projects to,
+    // for shaders drawing the scene with the camera of the last `compute`.
+    pub fn visibility(&self, position: &Vec3) -> f32 {
+        let clip = self.view_projection * position.push(1f32);
+        let window = self.viewport.to_window(&(clip.xyz() / clip.w));
+        self.get(window.x.floor() as i32, window.y.floor() as i32)
+    }
+}
@@ -0,0 +1,135 @@
+use crate::CompareFunction;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StencilOperation {
+    Keep,
+    Zero,
+    Replace,
+    IncrementClamp,
+    DecrementClamp,
+    IncrementWrap,
+    DecrementWrap,
+    Invert,
+}
+
+impl StencilOperation {
+    pub fn apply(&self, value: u8, reference: u8) -> u8 {
+        match self {
+            StencilOperation::Keep => value,
+            StencilOperation::Zero => 0,
+            StencilOperation::Replace => reference,
+            StencilOperation::IncrementClamp => value.saturating_add(1),
+            StencilOperation::DecrementClamp => value.saturating_sub(1),
+            StencilOperation::IncrementWrap => value.wrapping_add(1),
+            StencilOperation::DecrementWrap => value.wrapping_sub(1),
+            StencilOperation::Invert => !value,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct StencilFaceState {
+    pub compare: CompareFunction,
+    pub fail_op: StencilOperation,
+    pub depth_fail_op: StencilOperation,
+    pub pass_op: StencilOperation,
+}
+
+impl Default for StencilFaceState {
+    fn default() -> Self {
+        StencilFaceState {
+            compare: CompareFunction::Always,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op: StencilOperation::Keep,
+        }
+    }
+}
+
+// Front and back faces are told apart with `RasterizerState::front_face`, lines are front facing.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct StencilState {
+    pub enabled: bool,
+    pub reference: u8,
+    pub read_mask: u8,
+    pub write_mask: u8,
+    pub front: StencilFaceState,
+    pub back: StencilFaceState,
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        StencilState {
+            enabled: false,
+            reference: 0,
+            read_mask: 0xff,
+            write_mask: 0xff,
+            front: StencilFaceState::default(),
+            back: StencilFaceState::default(),
+        }
+    }
+}
+
+impl StencilState {
+    pub fn face(&self, front_facing: bool) -> &StencilFaceState {
+        if front_facing {
+            &self.front
+        } else {
+            &self.back
+        }
+    }
+
+    // Compares the masked reference against the masked stored value.
+    pub fn test(&self, front_facing: bool, stored: u8) -> bool {
+        self.face(front_facing)
+            .compare
+            .test(self.reference & self.read_mask, stored & self.read_mask)
+    }
+
+    // Applies `op` to `stored`, only the bits in `write_mask` are changed.
+    pub fn update(&self, op: StencilOperation, stored: u8) -> u8 {
+        let value = op.apply(stored, self.reference);
+        (stored & !self.write_mask) | (value & self.write_mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stencil_operation() {
+        let state = StencilState {
+            reference: 0x5a,
+            write_mask: 0x0f,
+            ..Default::default()
+        };
+        assert_eq!(StencilOperation::Keep.apply(7, 0x5a), 7);
+        assert_eq!(StencilOperation::Zero.apply(7, 0x5a), 0);
+        assert_eq!(StencilOperation::Replace.apply(7, 0x5a), 0x5a);
+        assert_eq!(StencilOperation::IncrementClamp.apply(255, 0), 255);
+        assert_eq!(StencilOperation::DecrementClamp.apply(0, 0), 0);
+        assert_eq!(StencilOperation::IncrementWrap.apply(255, 0), 0);
+        assert_eq!(StencilOperation::DecrementWrap.apply(0, 0), 255);
+        assert_eq!(StencilOperation::Invert.apply(0x0f, 0), 0xf0);
+        assert_eq!(state.update(StencilOperation::Replace, 0xf0), 0xfa);
+        assert_eq!(state.update(StencilOperation::Invert, 0xf0), 0xff);
+
+        let state = StencilState {
+            reference: 0x12,
+            read_mask: 0x0f,
+            front: StencilFaceState {
+                compare: CompareFunction::Equal,
+                ..Default::default()
+            },
+            back: StencilFaceState {
+                compare: CompareFunction::Never,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(state.test(true, 0xf2));
+        assert!(!state.test(true, 0xf3));
+        assert!(!state.test(false, 0xf2));
+    }
+}
@@ -1,10 +1,11 @@
-use crate::{image_read, image_write, Color, Colorf, ImageReadError, ImageWriteError};
+use crate::{image_read, image_write, Color, ColorSpace, Colorf, ImageReadError, ImageWriteError};
 use std::path::Path;
 use std::slice;
 
 pub struct Texture2D {
     pixels: Vec<Color>,
     border_color: Color,
+    color_space: ColorSpace,
     pub width: i32,
     pub height: i32,
 }
@@ -59,11 +60,13 @@ impl Texture2D {
         Ok(Texture2D {
             pixels: vec![*color; (width * height) as usize],
             border_color: Color::transparent(),
+            color_space: ColorSpace::Linear,
             width,
             height,
         })
     }
 
+    // Loads a texture holding linear data, such as a normal or specular map.
     pub fn load(filepath: impl AsRef<Path>) -> Result<Self, Texture2DError> {
         let mut width = 0;
         let mut height = 0;
@@ -71,11 +74,28 @@ impl Texture2D {
         Ok(Texture2D {
             pixels,
             border_color: Color::transparent(),
+            color_space: ColorSpace::Linear,
             width,
             height,
         })
     }
 
+    // Loads a texture holding sRGB encoded colors, such as a diffuse or glow map.
+    pub fn load_srgb(filepath: impl AsRef<Path>) -> Result<Self, Texture2DError> {
+        let mut texture = Self::load(filepath)?;
+        texture.set_color_space(ColorSpace::Srgb);
+        Ok(texture)
+    }
+
+    // Sampling converts texels from `color_space` to linear values.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
     pub fn to_u8_ptr(&self) -> *const u8 {
         self.pixels.as_ptr() as *const u8
     }
@@ -151,19 +171,19 @@ impl Texture2D {
     ) -> Colorf {
         let (x, y, use_border_color) = Self::wrap_coord(x, 1f32 - y, wrap_mode);
         if use_border_color {
-            return self.border_color.into();
+            return self.color_space.decode(&self.border_color);
         }
         match filter_mode {
             Texture2DFilterMode::Nearest => {
-                let x = (x * self.width as f32).round() as i32;
-                let y = (y * self.height as f32).round() as i32;
-                (*self.get_color(x, y).unwrap()).into()
+                let x = ((x * self.width as f32) as i32).min(self.width - 1);
+                let y = ((y * self.height as f32) as i32).min(self.height - 1);
+                self.color_space.decode(self.get_color(x, y).unwrap())
             }
             Texture2DFilterMode::Linear => {
-                let x_min = (x * self.width as f32).floor() as i32;
-                let y_min = (y * self.height as f32).floor() as i32;
-                let x_max = (x * self.width as f32).ceil() as i32;
-                let y_max = (y * self.height as f32).ceil() as i32;
+                let x_min = ((x * self.width as f32).floor() as i32).min(self.width - 1);
+                let y_min = ((y * self.height as f32).floor() as i32).min(self.height - 1);
+                let x_max = ((x * self.width as f32).ceil() as i32).min(self.width - 1);
+                let y_max = ((y * self.height as f32).ceil() as i32).min(self.height - 1);
                 let x = x * self.width as f32;
                 let y = y * self.height as f32;
                 let xt = if x_min == x_max {
@@ -176,10 +196,18 @@ impl Texture2D {
                 } else {
                     (y - y_min as f32) / (y_max - y_min) as f32
                 };
-                let color1: Colorf = (*(self.get_color(x_min, y_min).unwrap())).into();
-                let color2: Colorf = (*(self.get_color(x_max, y_min).unwrap())).into();
-                let color3: Colorf = (*(self.get_color(x_min, y_max).unwrap())).into();
-                let color4: Colorf = (*(self.get_color(x_max, y_max).unwrap())).into();
+                let color1 = self
+                    .color_space
+                    .decode(self.get_color(x_min, y_min).unwrap());
+                let color2 = self
+                    .color_space
+                    .decode(self.get_color(x_max, y_min).unwrap());
+                let color3 = self
+                    .color_space
+                    .decode(self.get_color(x_min, y_max).unwrap());
+                let color4 = self
+                    .color_space
+                    .decode(self.get_color(x_max, y_max).unwrap());
                 color1 * (1f32 - xt) * (1f32 - yt)
                     + color2 * xt * (1f32 - yt)
                     + color3 * (1f32 - xt) * yt
@@ -261,4 +289,37 @@ mod tests {
             Texture2D::wrap_coord(1.2f32, 1.6f32, Texture2DWrapMode::MirroredRepeat)
         );
     }
+
+    #[test]
+    fn test_texel_at_edges() {
+        let mut texture = Texture2D::create(2, 2).unwrap();
+        texture.pixels = vec![Color::red(), Color::green(), Color::blue(), Color::white()];
+        let sample = |u: f32, v: f32, filter_mode: Texture2DFilterMode| -> Color {
+            texture
+                .texture(u, v, Texture2DWrapMode::ClampToEdge, filter_mode)
+                .into()
+        };
+        // Nearest picks the texel the coordinate falls in, not the closest texel corner.
+        assert_eq!(
+            sample(0.4f32, 0.9f32, Texture2DFilterMode::Nearest),
+            Color::red()
+        );
+        assert_eq!(
+            sample(0.6f32, 0.9f32, Texture2DFilterMode::Nearest),
+            Color::green()
+        );
+        // u = 1 and v = 0 lie on the far edge and must stay inside the texture.
+        assert_eq!(
+            sample(1f32, 0f32, Texture2DFilterMode::Nearest),
+            Color::white()
+        );
+        assert_eq!(
+            sample(1f32, 0f32, Texture2DFilterMode::Linear),
+            Color::white()
+        );
+        assert_eq!(
+            sample(1f32, 1f32, Texture2DFilterMode::Linear),
+            Color::green()
+        );
+    }
 }
@@ -0,0 +1,90 @@
+use crate::Vec3;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ToneMapOperator {
+    // Saturates every channel at 1.
+    Clamp,
+    Reinhard,
+    // Reinhard with `white_point` mapped to 1 instead of infinity.
+    ExtendedReinhard { white_point: f32 },
+    // Narkowicz's fit of the ACES filmic curve.
+    Aces,
+}
+
+// Maps linear HDR colors to [0, 1], see `Framebuffer::tone_map_to`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    // In stops, colors are scaled by 2^exposure before the operator is applied.
+    pub exposure: f32,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self::new(ToneMapOperator::Reinhard)
+    }
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator) -> Self {
+        ToneMapping {
+            operator,
+            exposure: 0f32,
+        }
+    }
+
+    pub fn with_exposure(&self, exposure: f32) -> Self {
+        ToneMapping { exposure, ..*self }
+    }
+
+    pub fn map(&self, color: &Vec3) -> Vec3 {
+        let color = color.map(|value| value.max(0f32)) * self.exposure.exp2();
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => color.map(|value| value / (1f32 + value)),
+            ToneMapOperator::ExtendedReinhard { white_point } => {
+                let white_sq = white_point * white_point;
+                color.map(|value| value * (1f32 + value / white_sq) / (1f32 + value))
+            }
+            ToneMapOperator::Aces => color.map(|value| {
+                (value * (2.51f32 * value + 0.03f32))
+                    / (value * (2.43f32 * value + 0.59f32) + 0.14f32)
+            }),
+        };
+        mapped.map(|value| value.clamp(0f32, 1f32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_tone_mapping() {
+        let color = Vec3::new(0f32, 1f32, 4f32);
+        let clamp = ToneMapping::new(ToneMapOperator::Clamp);
+        assert_relative_eq!(clamp.map(&color), Vec3::new(0f32, 1f32, 1f32));
+        assert_relative_eq!(
+            clamp.with_exposure(-2f32).map(&color),
+            Vec3::new(0f32, 0.25f32, 1f32)
+        );
+        let reinhard = ToneMapping::new(ToneMapOperator::Reinhard);
+        assert_relative_eq!(reinhard.map(&color), Vec3::new(0f32, 0.5f32, 0.8f32));
+        let extended = ToneMapping::new(ToneMapOperator::ExtendedReinhard { white_point: 4f32 });
+        assert_relative_eq!(extended.map(&color).z, 1f32);
+        assert!(extended.map(&color).y > 0.5f32);
+        let aces = ToneMapping::new(ToneMapOperator::Aces);
+        assert_relative_eq!(aces.map(&color).x, 0f32);
+        assert_relative_eq!(aces.map(&Vec3::repeat(1000f32)).x, 1f32, epsilon = 1e-2);
+        // Every operator is monotonic.
+        for tone_mapping in [clamp, reinhard, extended, aces] {
+            let mut previous = -1f32;
+            for i in 0..100 {
+                let value = tone_mapping.map(&Vec3::repeat(i as f32 * 0.1f32)).x;
+                assert!(value >= previous);
+                previous = value;
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{Vec2i, Vec3};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    // Window depth that NDC z = -1 and z = 1 are mapped to.
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(0, 0, 0, 0)
+    }
+}
+
+impl Viewport {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+            min_depth: -1f32,
+            max_depth: 1f32,
+        }
+    }
+
+    pub fn with_depth_range(&self, min_depth: f32, max_depth: f32) -> Self {
+        Viewport {
+            min_depth,
+            max_depth,
+            ..*self
+        }
+    }
+
+    pub(crate) fn clamp_depth(&self, depth: f32) -> f32 {
+        depth.clamp(
+            self.min_depth.min(self.max_depth),
+            self.min_depth.max(self.max_depth),
+        )
+    }
+
+    // Maps NDC to window coordinates with y pointing down and z in the depth range.
+    pub fn to_window(&self, ndc: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.x as f32 + (ndc.x + 1f32) / 2f32 * self.width as f32,
+            self.y as f32 + (-ndc.y + 1f32) / 2f32 * self.height as f32,
+            self.min_depth + (ndc.z + 1f32) / 2f32 * (self.max_depth - self.min_depth),
+        )
+    }
+
+    pub fn to_ndc(&self, window: &Vec3) -> Vec3 {
+        Vec3::new(
+            (window.x - self.x as f32) / self.width as f32 * 2f32 - 1f32,
+            1f32 - (window.y - self.y as f32) / self.height as f32 * 2f32,
+            (window.z - self.min_depth) / (self.max_depth - self.min_depth) * 2f32 - 1f32,
+        )
+    }
+}
+
+// A rectangle in window coordinates outside of which fragments are discarded.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Scissor {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Scissor {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Scissor {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // Returns the inclusive pixel bounds shared by both rectangles, which may be empty.
+    pub(crate) fn intersect(&self, min: &Vec2i, max: &Vec2i) -> (Vec2i, Vec2i) {
+        (
+            Vec2i::new(min.x.max(self.x), min.y.max(self.y)),
+            Vec2i::new(
+                max.x.min(self.x + self.width - 1),
+                max.y.min(self.y + self.height - 1),
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_to_window() {
+        let viewport = Viewport::new(10, 20, 100, 50);
+        let ndc = Vec3::new(-1f32, 1f32, 0.5f32);
+        assert_relative_eq!(viewport.to_window(&ndc), Vec3::new(10f32, 20f32, 0.5f32));
+        let ndc = Vec3::new(0.5f32, -0.5f32, -0.25f32);
+        assert_relative_eq!(
+            viewport.to_window(&ndc),
+            Vec3::new(85f32, 57.5f32, -0.25f32)
+        );
+        let viewport = viewport.with_depth_range(0f32, 1f32);
+        let window = viewport.to_window(&ndc);
+        assert_relative_eq!(window, Vec3::new(85f32, 57.5f32, 0.375f32));
+        assert_relative_eq!(viewport.to_ndc(&window), ndc);
+    }
+}
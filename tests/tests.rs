@@ -1,4 +1,16 @@
-use tinyrenderer_rs::{Color, Framebuffer};
+use approx::assert_relative_eq;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tinyrenderer_rs::{
+    draw_deferred_lighting, draw_line, draw_triangles, AttachmentFormat, Attenuation, BlendState,
+    Bloom, Camera, Color, ColorSpace, Colorf, CompareFunction, CullMode, DepthState, Framebuffer,
+    FramebufferError, FrontFace, Fxaa, GBuffer, GBufferShader, Light, Mat4, Material, Model,
+    NormalMap, NormalMapSpace, PbrMaterial, PostProcess, PostProcessPass, RasterizerState, Scissor,
+    Shader, ShadowMap, Ssao, StencilFaceState, StencilOperation, StencilState, Texture2D,
+    Texture2DFilterMode, Texture2DWrapMode, ToneMapOperator, ToneMapping, Vec2, Vec3, Vec4,
+    Viewport, GBUFFER_NORMAL,
+};
 
 #[test]
 fn test_framebuffer() {
@@ -7,3 +19,1506 @@ fn test_framebuffer() {
     framebuffer.set_color(10, 10, &Color::red());
     assert_eq!(Color::red(), *framebuffer.get_color(10, 10).unwrap());
 }
+
+struct QuadShader {
+    verts: [Vec4; 6],
+}
+
+impl Shader for QuadShader {
+    type Varying = f32;
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        let pos = self.verts[face * 3 + vert];
+        (pos, pos.x)
+    }
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf> {
+        if *varying < 0f32 {
+            None
+        } else {
+            Some(Colorf::new(1f32, 0f32, 0f32, 1f32))
+        }
+    }
+}
+
+fn quad_shader() -> QuadShader {
+    QuadShader {
+        verts: [
+            Vec4::new(-1f32, -1f32, 0f32, 1f32),
+            Vec4::new(1f32, -1f32, 0f32, 1f32),
+            Vec4::new(1f32, 1f32, 0f32, 1f32),
+            Vec4::new(-1f32, -1f32, 0f32, 1f32),
+            Vec4::new(1f32, 1f32, 0f32, 1f32),
+            Vec4::new(-1f32, 1f32, 0f32, 1f32),
+        ],
+    }
+}
+
+#[test]
+fn test_shader() {
+    let mut framebuffer = Framebuffer::create(64, 64).unwrap();
+    let shader = quad_shader();
+    draw_triangles(&mut framebuffer, &shader, 2);
+    assert_eq!(
+        Color::transparent(),
+        *framebuffer.get_color(16, 32).unwrap()
+    );
+    assert_eq!(Color::red(), *framebuffer.get_color(48, 32).unwrap());
+}
+
+#[test]
+fn test_face_culling() {
+    let mut framebuffer = Framebuffer::create(64, 64).unwrap();
+    let shader = quad_shader();
+    let cases = [
+        (CullMode::None, FrontFace::CounterClockwise, 0),
+        (CullMode::Back, FrontFace::CounterClockwise, 0),
+        (CullMode::Front, FrontFace::CounterClockwise, 2),
+        (CullMode::Back, FrontFace::Clockwise, 2),
+        (CullMode::Front, FrontFace::Clockwise, 0),
+    ];
+    for (cull_mode, front_face, culled) in cases {
+        framebuffer.clear_color();
+        framebuffer.clear_depth();
+        framebuffer.reset_stats();
+        framebuffer.set_rasterizer_state(&RasterizerState {
+            cull_mode,
+            front_face,
+            ..Default::default()
+        });
+        draw_triangles(&mut framebuffer, &shader, 2);
+        assert_eq!(framebuffer.stats().triangles, 2);
+        assert_eq!(framebuffer.stats().culled_triangles, culled);
+        let expected = if culled == 0 {
+            Color::red()
+        } else {
+            Color::transparent()
+        };
+        assert_eq!(expected, *framebuffer.get_color(48, 32).unwrap());
+    }
+}
+
+struct TextureShader<'a> {
+    model: &'a Model,
+    texture: &'a Texture2D,
+    mvp: Mat4,
+}
+
+impl Shader for TextureShader<'_> {
+    type Varying = Vec2;
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        let i = face * 3 + vert;
+        (self.mvp * self.model.verts[i].push(1f32), self.model.uvs[i])
+    }
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf> {
+        Some(self.texture.texture(
+            varying.x,
+            varying.y,
+            Texture2DWrapMode::Repeat,
+            Texture2DFilterMode::Nearest,
+        ))
+    }
+}
+
+#[test]
+fn test_perspective_correct_interpolation() {
+    let model = Model::load("assets/floor/floor.obj").unwrap();
+    let texture = Texture2D::load("assets/floor/floor_diffuse.png").unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_3, 1f32, 0.1f32, 100f32);
+    camera.look_at(
+        &Vec3::new(0f32, 0.5f32, 2.5f32),
+        &Vec3::new(0f32, -1f32, 0f32),
+        &Vec3::y(),
+    );
+    let shader = TextureShader {
+        model: &model,
+        texture: &texture,
+        mvp: camera.view_projection_matrix(),
+    };
+    let mut framebuffer = Framebuffer::create_init_color(256, 256, &Color::black()).unwrap();
+    draw_triangles(&mut framebuffer, &shader, model.verts.len() / 3);
+
+    // The second checker column boundary converges towards the vanishing point, with affine
+    // interpolation it bends where it crosses the diagonal shared by the two floor triangles.
+    let mut boundary: Vec<(i32, i32)> = Vec::new();
+    for y in 0..framebuffer.height {
+        let mut transitions: Vec<i32> = Vec::new();
+        for x in 1..framebuffer.width {
+            let c0 = *framebuffer.get_color(x - 1, y).unwrap();
+            let c1 = *framebuffer.get_color(x, y).unwrap();
+            if c0 != c1 && c0 != Color::black() && c1 != Color::black() {
+                transitions.push(x);
+            }
+        }
+        if transitions.len() == 9 {
+            boundary.push((y, transitions[1]));
+        }
+    }
+    assert!(boundary.len() > 64);
+    let (y0, x0) = boundary[0];
+    let (y1, x1) = boundary[boundary.len() - 1];
+    for (y, x) in boundary {
+        let expected = x0 as f32 + (x1 - x0) as f32 * (y - y0) as f32 / (y1 - y0) as f32;
+        assert!((x as f32 - expected).abs() <= 1.5f32);
+    }
+}
+
+#[test]
+fn test_near_plane_clipping() {
+    let model = Model::load("assets/floor/floor.obj").unwrap();
+    let texture = Texture2D::load("assets/floor/floor_diffuse.png").unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_2, 1f32, 0.1f32, 100f32);
+    camera.look_at(
+        &Vec3::new(0f32, -0.5f32, 0f32),
+        &Vec3::new(0f32, -0.5f32, -1f32),
+        &Vec3::y(),
+    );
+    let shader = TextureShader {
+        model: &model,
+        texture: &texture,
+        mvp: camera.view_projection_matrix(),
+    };
+    let mut framebuffer = Framebuffer::create_init_color(64, 64, &Color::black()).unwrap();
+    draw_triangles(&mut framebuffer, &shader, model.verts.len() / 3);
+    for x in 0..framebuffer.width {
+        assert_eq!(Color::black(), *framebuffer.get_color(x, 0).unwrap());
+        assert_ne!(
+            Color::black(),
+            *framebuffer.get_color(x, framebuffer.height - 1).unwrap()
+        );
+    }
+}
+
+struct CountingShader {
+    verts: Vec<Vec4>,
+    color: Colorf,
+    fragments: AtomicUsize,
+}
+
+impl Shader for CountingShader {
+    type Varying = ();
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        (self.verts[face * 3 + vert], ())
+    }
+
+    fn fragment(&self, _varying: &Self::Varying) -> Option<Colorf> {
+        self.fragments.fetch_add(1, Ordering::Relaxed);
+        Some(self.color)
+    }
+}
+
+#[test]
+fn test_watertight_rasterization() {
+    // A full-screen quad split into a grid of triangles whose inner vertices are jittered to
+    // sub-pixel positions. With additive blending, every pixel must be touched exactly once.
+    const GRID: usize = 7;
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut grid: Vec<Vec4> = Vec::new();
+    for j in 0..=GRID {
+        for i in 0..=GRID {
+            let mut x = i as f32 / GRID as f32 * 2f32 - 1f32;
+            let mut y = j as f32 / GRID as f32 * 2f32 - 1f32;
+            if i != 0 && i != GRID {
+                x += rng.gen_range(-0.1f32..0.1f32);
+            }
+            if j != 0 && j != GRID {
+                y += rng.gen_range(-0.1f32..0.1f32);
+            }
+            grid.push(Vec4::new(x, y, 0f32, 1f32));
+        }
+    }
+    let mut verts: Vec<Vec4> = Vec::new();
+    for j in 0..GRID {
+        for i in 0..GRID {
+            let p00 = grid[j * (GRID + 1) + i];
+            let p10 = grid[j * (GRID + 1) + i + 1];
+            let p01 = grid[(j + 1) * (GRID + 1) + i];
+            let p11 = grid[(j + 1) * (GRID + 1) + i + 1];
+            verts.extend_from_slice(&[p00, p10, p11, p00, p11, p01]);
+        }
+    }
+    let shader = CountingShader {
+        verts,
+        color: Colorf::new(0.25f32, 0.25f32, 0.25f32, 0.25f32),
+        fragments: AtomicUsize::new(0),
+    };
+    let mut framebuffer = Framebuffer::create(61, 53).unwrap();
+    framebuffer.set_depth_test(false);
+    framebuffer.set_blend_state(&BlendState::additive());
+    draw_triangles(&mut framebuffer, &shader, shader.verts.len() / 3);
+    assert_eq!(
+        shader.fragments.load(Ordering::Relaxed),
+        (framebuffer.width * framebuffer.height) as usize
+    );
+    for y in 0..framebuffer.height {
+        for x in 0..framebuffer.width {
+            assert_eq!(
+                Color::new(64, 64, 64, 64),
+                *framebuffer.get_color(x, y).unwrap()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_tiled_rasterization() {
+    let model = Model::load("assets/african_head/african_head.obj").unwrap();
+    let texture = Texture2D::load("assets/african_head/african_head_diffuse.png").unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_4, 1f32, 0.1f32, 100f32);
+    camera.look_at(&Vec3::new(1f32, 0.5f32, 3f32), &Vec3::zeros(), &Vec3::y());
+    let shader = TextureShader {
+        model: &model,
+        texture: &texture,
+        mvp: camera.view_projection_matrix(),
+    };
+    let render = |threads: usize| {
+        let mut framebuffer = Framebuffer::create_init_color(200, 150, &Color::black()).unwrap();
+        framebuffer.set_rasterizer_state(&RasterizerState {
+            threads,
+            ..Default::default()
+        });
+        draw_triangles(&mut framebuffer, &shader, model.verts.len() / 3);
+        framebuffer
+    };
+    let serial = render(1);
+    for threads in [2, 3, 8, 1000] {
+        let tiled = render(threads);
+        assert_eq!(serial.to_u8_slice().unwrap(), tiled.to_u8_slice().unwrap());
+        for y in 0..serial.height {
+            for x in 0..serial.width {
+                assert_eq!(
+                    serial.get_depth(x, y).to_bits(),
+                    tiled.get_depth(x, y).to_bits()
+                );
+            }
+        }
+    }
+
+    // Empty framebuffers have no tiles to draw.
+    let mut framebuffer = Framebuffer::create(0, 0).unwrap();
+    framebuffer.set_rasterizer_state(&RasterizerState {
+        threads: 4,
+        ..Default::default()
+    });
+    draw_triangles(&mut framebuffer, &shader, model.verts.len() / 3);
+}
+
+#[test]
+fn test_multisample() {
+    assert!(Framebuffer::create_multisample(16, 16, 3).is_err());
+    for samples in [1, 2, 4, 8] {
+        let shader = CountingShader {
+            verts: vec![
+                Vec4::new(-0.9f32, -0.8f32, 0f32, 1f32),
+                Vec4::new(0.7f32, -0.9f32, 0f32, 1f32),
+                Vec4::new(0.1f32, 0.85f32, 0f32, 1f32),
+            ],
+            color: Colorf::new(1f32, 1f32, 1f32, 1f32),
+            fragments: AtomicUsize::new(0),
+        };
+        let mut framebuffer =
+            Framebuffer::create_multisample_init_color(32, 32, samples, &Color::black()).unwrap();
+        draw_triangles(&mut framebuffer, &shader, 1);
+        let resolved = framebuffer.resolve();
+        // Raw samples are not pixels, they are only read back once resolved.
+        assert_eq!(framebuffer.to_u8_slice().is_ok(), samples == 1);
+        assert_eq!(framebuffer.to_u32_slice().is_ok(), samples == 1);
+        assert!(resolved.to_u32_slice().is_ok());
+        let mut covered = 0;
+        let mut partial = 0;
+        for y in 0..resolved.height {
+            for x in 0..resolved.width {
+                let color = *resolved.get_color(x, y).unwrap();
+                if color != Color::black() {
+                    covered += 1;
+                }
+                if color != Color::black() && color != Color::white() {
+                    partial += 1;
+                }
+            }
+        }
+        // Fragments are shaded once per pixel regardless of how many samples they cover.
+        assert_eq!(shader.fragments.load(Ordering::Relaxed), covered);
+        if samples == 1 {
+            assert_eq!(partial, 0);
+        } else {
+            assert!(partial > 0);
+        }
+    }
+}
+
+#[test]
+fn test_alpha_blending() {
+    let shader = CountingShader {
+        verts: quad_shader().verts.to_vec(),
+        color: Colorf::new(1f32, 0f32, 0f32, 0.5f32),
+        fragments: AtomicUsize::new(0),
+    };
+    let mut framebuffer = Framebuffer::create_init_color(8, 8, &Color::blue()).unwrap();
+    framebuffer.set_depth_test(false);
+    draw_triangles(&mut framebuffer, &shader, 2);
+    assert_eq!(
+        Color::new(255, 0, 0, 128),
+        *framebuffer.get_color(4, 4).unwrap()
+    );
+    framebuffer.clear_color_with(&Color::blue());
+    framebuffer.set_blend_state(&BlendState::alpha());
+    draw_triangles(&mut framebuffer, &shader, 2);
+    assert_eq!(
+        Color::new(128, 0, 128, 255),
+        *framebuffer.get_color(4, 4).unwrap()
+    );
+}
+
+#[test]
+fn test_depth_state() {
+    let quad = |z: f32, color: Colorf| CountingShader {
+        verts: quad_shader()
+            .verts
+            .iter()
+            .map(|v| Vec4::new(v.x, v.y, z, 1f32))
+            .collect(),
+        color,
+        fragments: AtomicUsize::new(0),
+    };
+    let mut framebuffer = Framebuffer::create_init_color(8, 8, &Color::black()).unwrap();
+    framebuffer.set_viewport(&framebuffer.viewport().with_depth_range(0f32, 1f32));
+    framebuffer.clear_depth_with(0f32);
+
+    // With depth writes off the depth buffer is left untouched.
+    framebuffer.set_depth_state(&DepthState {
+        write_enabled: false,
+        ..Default::default()
+    });
+    draw_triangles(
+        &mut framebuffer,
+        &quad(0.5f32, Colorf::new(1f32, 0f32, 0f32, 1f32)),
+        2,
+    );
+    assert_eq!(0f32, framebuffer.get_depth(4, 4));
+    framebuffer.set_depth_state(&DepthState::default());
+    draw_triangles(
+        &mut framebuffer,
+        &quad(0.5f32, Colorf::new(1f32, 0f32, 0f32, 1f32)),
+        2,
+    );
+    assert_eq!(0.75f32, framebuffer.get_depth(4, 4));
+
+    // A decal at exactly the same depth only passes with an equal compare.
+    let decal = quad(0.5f32, Colorf::new(0f32, 1f32, 0f32, 1f32));
+    draw_triangles(&mut framebuffer, &decal, 2);
+    assert_eq!(Color::red(), *framebuffer.get_color(4, 4).unwrap());
+    framebuffer.set_depth_state(&DepthState {
+        write_enabled: false,
+        compare: CompareFunction::Equal,
+        ..Default::default()
+    });
+    draw_triangles(&mut framebuffer, &decal, 2);
+    assert_eq!(Color::green(), *framebuffer.get_color(4, 4).unwrap());
+
+    framebuffer.set_depth_state(&DepthState::default());
+    draw_triangles(
+        &mut framebuffer,
+        &quad(0.99f32, Colorf::new(0f32, 0f32, 1f32, 1f32)),
+        2,
+    );
+    assert_eq!(Color::blue(), *framebuffer.get_color(4, 4).unwrap());
+    framebuffer.set_depth_state(&DepthState {
+        compare: CompareFunction::Never,
+        ..Default::default()
+    });
+    draw_triangles(&mut framebuffer, &decal, 2);
+    assert_eq!(Color::blue(), *framebuffer.get_color(4, 4).unwrap());
+}
+
+#[test]
+fn test_stencil() {
+    let quad = |verts: Vec<Vec4>, color: Colorf| CountingShader {
+        verts,
+        color,
+        fragments: AtomicUsize::new(0),
+    };
+    let mut framebuffer = Framebuffer::create_init_color(16, 16, &Color::black()).unwrap();
+    framebuffer.set_depth_test(false);
+
+    // Mark the left half of the framebuffer, then only draw where the mark is set.
+    let left = quad(
+        quad_shader()
+            .verts
+            .iter()
+            .map(|v| Vec4::new(v.x.min(0f32), v.y, v.z, v.w))
+            .collect(),
+        Colorf::new(1f32, 0f32, 0f32, 1f32),
+    );
+    framebuffer.set_stencil_state(&StencilState {
+        enabled: true,
+        reference: 1,
+        front: StencilFaceState {
+            pass_op: StencilOperation::Replace,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    draw_triangles(&mut framebuffer, &left, 2);
+    assert_eq!(1, framebuffer.get_stencil(4, 8));
+    assert_eq!(0, framebuffer.get_stencil(12, 8));
+    let full = quad(
+        quad_shader().verts.to_vec(),
+        Colorf::new(0f32, 1f32, 0f32, 1f32),
+    );
+    framebuffer.set_stencil_state(&StencilState {
+        enabled: true,
+        reference: 1,
+        front: StencilFaceState {
+            compare: CompareFunction::NotEqual,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    draw_triangles(&mut framebuffer, &full, 2);
+    assert_eq!(Color::red(), *framebuffer.get_color(4, 8).unwrap());
+    assert_eq!(Color::green(), *framebuffer.get_color(12, 8).unwrap());
+
+    // Front faces increment and back faces decrement, with wrapping below zero.
+    framebuffer.clear_stencil();
+    let back: Vec<Vec4> = full
+        .verts
+        .chunks(3)
+        .flat_map(|v| [v[0], v[2], v[1]])
+        .collect();
+    framebuffer.set_stencil_state(&StencilState {
+        enabled: true,
+        front: StencilFaceState {
+            pass_op: StencilOperation::IncrementWrap,
+            ..Default::default()
+        },
+        back: StencilFaceState {
+            pass_op: StencilOperation::DecrementWrap,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    draw_triangles(&mut framebuffer, &quad(back.clone(), Colorf::zeros()), 2);
+    assert_eq!(255, framebuffer.get_stencil(8, 8));
+    draw_triangles(&mut framebuffer, &full, 2);
+    draw_triangles(&mut framebuffer, &full, 2);
+    assert_eq!(1, framebuffer.get_stencil(8, 8));
+
+    // Depth failures run the depth-fail operation only.
+    framebuffer.set_depth_test(true);
+    framebuffer.clear_depth_with(f32::MAX);
+    framebuffer.set_stencil_state(&StencilState {
+        enabled: true,
+        front: StencilFaceState {
+            depth_fail_op: StencilOperation::Zero,
+            pass_op: StencilOperation::Invert,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    draw_triangles(&mut framebuffer, &full, 2);
+    assert_eq!(0, framebuffer.get_stencil(8, 8));
+}
+
+#[test]
+fn test_viewport_and_scissor() {
+    let shader = CountingShader {
+        verts: quad_shader().verts.to_vec(),
+        color: Colorf::new(1f32, 1f32, 1f32, 1f32),
+        fragments: AtomicUsize::new(0),
+    };
+    let count_white = |framebuffer: &Framebuffer| {
+        let mut count = 0;
+        for y in 0..framebuffer.height {
+            for x in 0..framebuffer.width {
+                if *framebuffer.get_color(x, y).unwrap() == Color::white() {
+                    count += 1;
+                }
+            }
+        }
+        count
+    };
+    let mut framebuffer = Framebuffer::create_init_color(32, 16, &Color::black()).unwrap();
+    framebuffer.set_depth_test(false);
+
+    // Split screen, each half only receives its own view.
+    framebuffer.set_viewport(&Viewport::new(16, 0, 16, 16));
+    draw_triangles(&mut framebuffer, &shader, 2);
+    assert_eq!(16 * 16, count_white(&framebuffer));
+    assert_eq!(Color::black(), *framebuffer.get_color(15, 8).unwrap());
+    assert_eq!(Color::white(), *framebuffer.get_color(16, 8).unwrap());
+    framebuffer.clear_color_with(&Color::black());
+    framebuffer.set_viewport(&Viewport::new(0, 0, 16, 16));
+    draw_line(
+        &mut framebuffer,
+        &Vec4::new(-1f32, 0f32, 0f32, 1f32),
+        &Vec4::new(1f32, 0f32, 0f32, 1f32),
+        &Color::white(),
+    );
+    assert_eq!(16, count_white(&framebuffer));
+
+    // The scissor rectangle clips fragments without changing the screen mapping.
+    framebuffer.clear_color_with(&Color::black());
+    framebuffer.set_viewport(&Viewport::new(0, 0, 32, 16));
+    framebuffer.set_scissor(Some(&Scissor::new(4, 2, 8, 5)));
+    draw_triangles(&mut framebuffer, &shader, 2);
+    assert_eq!(8 * 5, count_white(&framebuffer));
+    assert_eq!(Color::white(), *framebuffer.get_color(4, 2).unwrap());
+    assert_eq!(Color::white(), *framebuffer.get_color(11, 6).unwrap());
+    framebuffer.set_scissor(Some(&Scissor::new(20, 20, 8, 8)));
+    framebuffer.clear_color_with(&Color::black());
+    draw_triangles(&mut framebuffer, &shader, 2);
+    assert_eq!(0, count_white(&framebuffer));
+}
+
+struct MrtShader<'a> {
+    model: &'a Model,
+    texture: &'a Texture2D,
+    mvp: Mat4,
+}
+
+impl Shader for MrtShader<'_> {
+    type Varying = (Vec2, Vec3, Vec3);
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        let i = face * 3 + vert;
+        let pos = self.model.verts[i];
+        (
+            self.mvp * pos.push(1f32),
+            (self.model.uvs[i], self.model.norms[i], pos),
+        )
+    }
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf> {
+        Some(self.texture.texture(
+            varying.0.x,
+            varying.0.y,
+            Texture2DWrapMode::Repeat,
+            Texture2DFilterMode::Nearest,
+        ))
+    }
+
+    fn fragment_targets(&self, varying: &Self::Varying, targets: &mut [Option<Colorf>]) -> bool {
+        let normal = varying.1.normalize();
+        targets[0] = self.fragment(varying);
+        targets[1] = Some(varying.2.push(1f32));
+        targets[2] = Some(Colorf::new(
+            normal.x * 0.5f32 + 0.5f32,
+            normal.y * 0.5f32 + 0.5f32,
+            0f32,
+            0f32,
+        ));
+        targets[3] = Some(Colorf::new(1f32, 0f32, 0f32, 0f32));
+        true
+    }
+}
+
+#[test]
+fn test_multiple_render_targets() {
+    let model = Model::load("assets/african_head/african_head.obj").unwrap();
+    let texture = Texture2D::load("assets/african_head/african_head_diffuse.png").unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_4, 1f32, 0.1f32, 100f32);
+    camera.look_at(&Vec3::new(0f32, 0f32, 3f32), &Vec3::zeros(), &Vec3::y());
+    let mvp = camera.view_projection_matrix();
+
+    let mut forward = Framebuffer::create_init_color(128, 128, &Color::black()).unwrap();
+    let shader = TextureShader {
+        model: &model,
+        texture: &texture,
+        mvp,
+    };
+    draw_triangles(&mut forward, &shader, model.verts.len() / 3);
+
+    let mut gbuffer = Framebuffer::create_init_color(128, 128, &Color::black()).unwrap();
+    assert_eq!(
+        1,
+        gbuffer.add_attachment(AttachmentFormat::Rgba32F).unwrap()
+    );
+    assert_eq!(2, gbuffer.add_attachment(AttachmentFormat::Rg16).unwrap());
+    assert_eq!(3, gbuffer.add_attachment(AttachmentFormat::R32F).unwrap());
+    assert_eq!(4, gbuffer.attachment_count());
+    let shader = MrtShader {
+        model: &model,
+        texture: &texture,
+        mvp,
+    };
+    draw_triangles(&mut gbuffer, &shader, model.verts.len() / 3);
+
+    let mut covered = 0;
+    for y in 0..gbuffer.height {
+        for x in 0..gbuffer.width {
+            assert_eq!(
+                forward.get_color(x, y).unwrap(),
+                gbuffer.get_color(x, y).unwrap()
+            );
+            if gbuffer.get_attachment(3, x, y).unwrap().x == 0f32 {
+                assert_eq!(Vec4::zeros(), gbuffer.get_attachment(1, x, y).unwrap());
+                continue;
+            }
+            covered += 1;
+            let position = gbuffer.get_attachment(1, x, y).unwrap();
+            assert!(position.xyz().abs().max() <= 1f32);
+            let normal =
+                gbuffer.get_attachment(2, x, y).unwrap().xy() * 2f32 - Vec2::new(1f32, 1f32);
+            assert!(normal.norm() <= 1.001f32);
+        }
+    }
+    assert!(covered > 128 * 128 / 4);
+
+    // A shader that only writes the color buffer leaves the other attachments unchanged.
+    let snapshot = |framebuffer: &Framebuffer| -> Vec<Colorf> {
+        (1..framebuffer.attachment_count())
+            .flat_map(|index| {
+                (0..framebuffer.height * framebuffer.width).map(move |i| {
+                    framebuffer
+                        .get_attachment(index, i % framebuffer.width, i / framebuffer.width)
+                        .unwrap()
+                })
+            })
+            .collect()
+    };
+    let attachments = snapshot(&gbuffer);
+    gbuffer.clear_depth();
+    let forward_shader = TextureShader {
+        model: &model,
+        texture: &texture,
+        mvp,
+    };
+    draw_triangles(&mut gbuffer, &forward_shader, model.verts.len() / 3);
+    assert_eq!(attachments, snapshot(&gbuffer));
+
+    // Multisampled attachments are resolved with the color buffer.
+    let mut msaa =
+        Framebuffer::create_multisample_init_color(128, 128, 4, &Color::black()).unwrap();
+    for index in 1..gbuffer.attachment_count() {
+        msaa.add_attachment(gbuffer.attachment_format(index).unwrap())
+            .unwrap();
+    }
+    draw_triangles(&mut msaa, &shader, model.verts.len() / 3);
+    let resolved = msaa.resolve();
+    assert_eq!(4, resolved.attachment_count());
+    assert_relative_eq!(
+        resolved.get_attachment(1, 64, 64).unwrap(),
+        gbuffer.get_attachment(1, 64, 64).unwrap(),
+        epsilon = 1e-4f32
+    );
+    assert_relative_eq!(resolved.get_attachment(3, 64, 64).unwrap().x, 1f32);
+    assert_relative_eq!(resolved.get_attachment(3, 0, 0).unwrap().x, 0f32);
+    assert!(msaa
+        .resolve_to(&mut Framebuffer::create(128, 128).unwrap())
+        .is_err());
+
+    let dir = std::env::temp_dir();
+    for index in 0..gbuffer.attachment_count() {
+        let path = dir.join(format!("tinyrenderer_gbuffer_{}.png", index));
+        gbuffer.write_attachment(index, &path).unwrap();
+        assert!(path.exists());
+    }
+    assert!(gbuffer
+        .write_attachment(4, dir.join("tinyrenderer_gbuffer_4.png"))
+        .is_err());
+}
+
+struct LambertShader<'a> {
+    model: &'a Model,
+    texture: &'a Texture2D,
+    mvp: Mat4,
+    light_dir: Vec3,
+    light_intensity: f32,
+}
+
+impl Shader for LambertShader<'_> {
+    type Varying = (Vec2, Vec3);
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        let i = face * 3 + vert;
+        (
+            self.mvp * self.model.verts[i].push(1f32),
+            (self.model.uvs[i], self.model.norms[i]),
+        )
+    }
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf> {
+        let (uv, norm) = varying;
+        let intensity = (-norm.normalize()).dot(&self.light_dir) * self.light_intensity;
+        let intensity = Colorf::new(intensity, intensity, intensity, 1f32);
+        let mut color = self.texture.texture(
+            uv.x,
+            uv.y,
+            Texture2DWrapMode::ClampToEdge,
+            Texture2DFilterMode::Linear,
+        );
+        color.component_mul_assign(&intensity);
+        Some(color)
+    }
+}
+
+#[test]
+fn test_deferred_shading() {
+    let model = Model::load("assets/african_head/african_head.obj").unwrap();
+    let texture = Texture2D::load("assets/african_head/african_head_diffuse.png").unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_4, 1f32, 0.1f32, 100f32);
+    camera.look_at(&Vec3::new(1f32, 0.5f32, 3f32), &Vec3::zeros(), &Vec3::y());
+    let light_dir = Vec3::new(-1f32, -1f32, -2f32).normalize();
+
+    let mut forward = Framebuffer::create_init_color(128, 128, &Color::black()).unwrap();
+    let shader = LambertShader {
+        model: &model,
+        texture: &texture,
+        mvp: camera.view_projection_matrix(),
+        light_dir,
+        light_intensity: 1f32,
+    };
+    draw_triangles(&mut forward, &shader, model.verts.len() / 3);
+
+    let mut gbuffer = GBuffer::create(128, 128).unwrap();
+    gbuffer.clear();
+    let shader = GBufferShader {
+        model: &model,
+        diffuse: &texture,
+        specular: None,
+        specular_intensity: 0f32,
+        mvp: camera.view_projection_matrix(),
+    };
+    draw_triangles(gbuffer.framebuffer_mut(), &shader, model.verts.len() / 3);
+
+    // One light, and the same light split in two, both match the forward render. The albedo
+    // is quantized to 8 bits in the G-buffer so channels may be off by one.
+    let white = Vec3::new(1f32, 1f32, 1f32);
+    for lights in [
+        vec![Light::directional(light_dir, white, 1f32)],
+        vec![
+            Light::directional(light_dir, white, 0.5f32),
+            Light::directional(light_dir, white, 0.5f32),
+        ],
+    ] {
+        let mut deferred = Framebuffer::create_init_color(128, 128, &Color::black()).unwrap();
+        draw_deferred_lighting(&mut deferred, &gbuffer, &camera, &lights).unwrap();
+        for y in 0..128 {
+            for x in 0..128 {
+                let a = forward.get_color(x, y).unwrap();
+                let b = deferred.get_color(x, y).unwrap();
+                for (a, b) in [(a.r, b.r), (a.g, b.g), (a.b, b.b), (a.a, b.a)] {
+                    assert!(a.abs_diff(b) <= 1, "({}, {}): {:?} != {:?}", x, y, a, b);
+                }
+            }
+        }
+    }
+
+    let mut deferred = Framebuffer::create(128, 128).unwrap();
+    let lights = [Light::directional(light_dir, white, 1f32)];
+    let mut degenerate = camera;
+    degenerate.look_at(&Vec3::zeros(), &Vec3::zeros(), &Vec3::y());
+    assert!(draw_deferred_lighting(&mut deferred, &gbuffer, &degenerate, &lights).is_err());
+    let degenerate = Camera::orthographic(0f32, 0f32, 0f32, 0f32, 1f32, 3f32);
+    assert!(draw_deferred_lighting(&mut deferred, &gbuffer, &degenerate, &lights).is_err());
+}
+
+// The deferred renderer matches the forward render of a `Material` with a specular map and no
+// ambient or emissive terms, in sRGB and HDR targets.
+#[test]
+fn test_deferred_specular() {
+    let model = Model::load("assets/african_head/african_head.obj").unwrap();
+    let diffuse = Texture2D::load_srgb("assets/african_head/african_head_diffuse.png").unwrap();
+    let specular = Texture2D::load("assets/african_head/african_head_spec.png").unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_4, 1f32, 0.1f32, 100f32);
+    camera.look_at(&Vec3::new(1f32, 0.5f32, 3f32), &Vec3::zeros(), &Vec3::y());
+    let mut material = Material::new(&diffuse).with_specular(&specular);
+    material.ambient = 0f32;
+    // The second light grazes the side of the head, away from the camera.
+    let white = Vec3::new(1f32, 1f32, 1f32);
+    let lights = [
+        Light::directional(Vec3::new(-1f32, -1f32, -2f32), white, 2f32),
+        Light::directional(Vec3::new(-1f32, 0f32, 0.3f32), white, 2f32),
+    ];
+
+    let mut gbuffer = GBuffer::create(128, 128).unwrap();
+    gbuffer.clear();
+    let shader = GBufferShader {
+        model: &model,
+        diffuse: &diffuse,
+        specular: Some(&specular),
+        specular_intensity: material.specular_intensity,
+        mvp: camera.view_projection_matrix(),
+    };
+    draw_triangles(gbuffer.framebuffer_mut(), &shader, model.verts.len() / 3);
+
+    let shader = MaterialShader {
+        model: &model,
+        material,
+        mvp: camera.view_projection_matrix(),
+        eye: camera.eye,
+        lights: &lights,
+    };
+    for hdr in [false, true] {
+        let create = || {
+            let mut framebuffer = if hdr {
+                Framebuffer::create_hdr(128, 128).unwrap()
+            } else {
+                Framebuffer::create(128, 128).unwrap()
+            };
+            framebuffer.set_color_space(ColorSpace::Srgb);
+            framebuffer.clear_color_with(&Color::black());
+            framebuffer
+        };
+        let mut forward = create();
+        draw_triangles(&mut forward, &shader, model.verts.len() / 3);
+        let mut deferred = create();
+        draw_deferred_lighting(&mut deferred, &gbuffer, &camera, &lights).unwrap();
+        let mut overexposed = 0;
+        for y in 0..128 {
+            for x in 0..128 {
+                let a = forward.get_attachment(0, x, y).unwrap();
+                let b = deferred.get_attachment(0, x, y).unwrap();
+                overexposed += (a.max() > 1f32) as usize;
+                // The albedo is quantized to 8 bits in the G-buffer.
+                assert!(
+                    (a - b).abs().max() <= 0.01f32 * a.max().max(1f32),
+                    "({}, {}): {:?} != {:?}",
+                    x,
+                    y,
+                    a,
+                    b
+                );
+            }
+        }
+        assert_eq!(overexposed > 0, hdr);
+    }
+}
+
+#[test]
+fn test_shadow_map() {
+    let floor = Model::load("assets/floor/floor.obj").unwrap();
+    let corners = [
+        Vec3::new(-0.5f32, 0f32, -0.5f32),
+        Vec3::new(-0.5f32, 0f32, 0.5f32),
+        Vec3::new(0.5f32, 0f32, 0.5f32),
+        Vec3::new(0.5f32, 0f32, -0.5f32),
+    ];
+    let occluder = Model::new(
+        [0, 1, 2, 0, 2, 3].iter().map(|&i| corners[i]).collect(),
+        vec![Vec2::zeros(); 6],
+        vec![Vec3::y(); 6],
+    );
+    let mut shadow_map = ShadowMap::create(64).unwrap();
+    shadow_map.set_light(&Vec3::new(0f32, -1f32, 0f32), &Vec3::zeros(), 2f32.sqrt());
+    shadow_map.draw(&floor);
+    shadow_map.draw(&occluder);
+    // Only depth is stored.
+    assert_eq!(shadow_map.framebuffer().attachment_format(0), None);
+    assert!(shadow_map.framebuffer().get_color(32, 32).is_err());
+    assert!(shadow_map.framebuffer().get_attachment(0, 32, 32).is_err());
+
+    // The occluder and the lit part of the floor don't shadow themselves.
+    assert_eq!(1f32, shadow_map.visibility(&Vec3::new(0f32, 0f32, 0f32)));
+    assert_eq!(0f32, shadow_map.visibility(&Vec3::new(0f32, -1f32, 0f32)));
+    for i in 0..=10 {
+        let t = -1f32 + i as f32 * 0.04f32;
+        assert_eq!(1f32, shadow_map.visibility(&Vec3::new(t, -1f32, t)));
+        assert_eq!(1f32, shadow_map.visibility(&Vec3::new(-t, -1f32, t)));
+    }
+    assert_eq!(1f32, shadow_map.visibility(&Vec3::new(5f32, -1f32, 0f32)));
+
+    // Percentage-closer filtering softens the shadow edge, without it the edge is hard.
+    let edge: Vec<Vec3> = (0..20)
+        .map(|i| Vec3::new(0.4f32 + i as f32 * 0.01f32, -1f32, 0f32))
+        .collect();
+    shadow_map.pcf_radius = 0;
+    assert!(edge
+        .iter()
+        .all(|p| [0f32, 1f32].contains(&shadow_map.visibility(p))));
+    shadow_map.pcf_radius = 2;
+    assert!(edge.iter().any(|p| {
+        let visibility = shadow_map.visibility(p);
+        visibility > 0f32 && visibility < 1f32
+    }));
+    let mut previous = 0f32;
+    for p in &edge {
+        let visibility = shadow_map.visibility(p);
+        assert!(visibility >= previous);
+        previous = visibility;
+    }
+}
+
+#[test]
+fn test_normal_mapping() {
+    let model = Model::load("assets/african_head/african_head.obj").unwrap();
+    let object = Texture2D::load("assets/african_head/african_head_nm.png").unwrap();
+    let tangent = Texture2D::load("assets/african_head/african_head_nm_tangent.png").unwrap();
+    let object = NormalMap::new(&object, NormalMapSpace::Object);
+    let tangent = NormalMap::new(&tangent, NormalMapSpace::Tangent);
+
+    // Both maps encode the same surface, so they agree at the centre of every face.
+    let mut total = 0f32;
+    let face_count = model.verts.len() / 3;
+    for face in 0..face_count {
+        let i = face * 3;
+        let uv = (model.uvs[i] + model.uvs[i + 1] + model.uvs[i + 2]) / 3f32;
+        let norm = model.norms[i] + model.norms[i + 1] + model.norms[i + 2];
+        let t = model.tangents[i] + model.tangents[i + 1] + model.tangents[i + 2];
+        let b = model.bitangents[i] + model.bitangents[i + 1] + model.bitangents[i + 2];
+        let n0 = object.normal(&uv, &norm, &t, &b);
+        let n1 = tangent.normal(&uv, &norm, &t, &b);
+        assert_relative_eq!(n1.norm(), 1f32, epsilon = 1e-4f32);
+        total += n0.dot(&n1);
+    }
+    assert!(total / face_count as f32 > 0.99f32);
+}
+
+struct MaterialShader<'a> {
+    model: &'a Model,
+    material: Material<'a>,
+    mvp: Mat4,
+    eye: Vec3,
+    lights: &'a [Light],
+}
+
+impl Shader for MaterialShader<'_> {
+    type Varying = (Vec2, Vec3, Vec3);
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        let i = face * 3 + vert;
+        let pos = self.model.verts[i];
+        (
+            self.mvp * pos.push(1f32),
+            (self.model.uvs[i], self.model.norms[i], pos),
+        )
+    }
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf> {
+        let (uv, norm, pos) = varying;
+        Some(self.material.shade(
+            uv,
+            pos,
+            &norm.normalize(),
+            &self.eye,
+            self.lights,
+            1f32,
+            |_| 1f32,
+        ))
+    }
+}
+
+#[test]
+fn test_multiple_lights() {
+    let floor = Model::load("assets/floor/floor.obj").unwrap();
+    let white = Texture2D::create_init_color(1, 1, &Color::white()).unwrap();
+    let mut material = Material::new(&white);
+    material.ambient = 0f32;
+    // Looking down at the floor, +x to the right and -z up.
+    let mut camera = Camera::orthographic(-1f32, 1f32, -1f32, 1f32, 1f32, 5f32);
+    camera.look_at(
+        &Vec3::new(0f32, 1f32, 0f32),
+        &Vec3::new(0f32, -1f32, 0f32),
+        &-Vec3::z(),
+    );
+    let lights = [
+        Light::point(
+            Vec3::new(-0.5f32, -0.5f32, 0f32),
+            Vec3::new(1f32, 0f32, 0f32),
+            1f32,
+        ),
+        Light::spot(
+            Vec3::new(0.5f32, 0f32, 0f32),
+            -Vec3::y(),
+            Vec3::new(0f32, 0f32, 1f32),
+            1f32,
+            0.3f32,
+            0.4f32,
+        )
+        .with_attenuation(Attenuation::new(1f32, 0f32, 0f32)),
+    ];
+    let mut framebuffer = Framebuffer::create_init_color(64, 64, &Color::black()).unwrap();
+    let shader = MaterialShader {
+        model: &floor,
+        material,
+        mvp: camera.view_projection_matrix(),
+        eye: camera.eye,
+        lights: &lights,
+    };
+    draw_triangles(&mut framebuffer, &shader, floor.verts.len() / 3);
+
+    // Below the point light, only red light at 1 / (1 + 0.5^2) arrives.
+    let color = framebuffer.get_color(16, 32).unwrap();
+    assert!(color.r.abs_diff(204) <= 1 && color.g == 0 && color.b == 0);
+    // Below the spot light, the blue light is unattenuated and some red still arrives.
+    let color = framebuffer.get_color(48, 32).unwrap();
+    assert!(color.b == 255 && color.r > 0 && color.r < 128 && color.g == 0);
+    // Outside of the spot cone, only the dimmer red light remains.
+    let color = framebuffer.get_color(61, 61).unwrap();
+    assert!(color.r > 0 && color.r < 128 && color.g == 0 && color.b == 0);
+}
+
+// Regression test for `Material`: renders `african_head` with its specular and glow maps and
+// compares it with an earlier render of this renderer. It does not check the images of the
+// tinyrenderer course, which use a reflected-vector Phong highlight and a different camera.
+#[test]
+fn test_african_head_regression() {
+    let model = Model::load("assets/african_head/african_head.obj").unwrap();
+    let diffuse = Texture2D::load("assets/african_head/african_head_diffuse.png").unwrap();
+    let specular = Texture2D::load("assets/african_head/african_head_spec.png").unwrap();
+    let glow = Texture2D::load("assets/african_head/african_head_glow.png").unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_4, 1f32, 0.1f32, 100f32);
+    camera.look_at(&Vec3::new(1f32, 1f32, 3f32), &Vec3::zeros(), &Vec3::y());
+    let lights = [Light::directional(
+        Vec3::new(-1f32, -1f32, -1f32),
+        Vec3::repeat(1f32),
+        1f32,
+    )];
+    let mut framebuffer = Framebuffer::create_init_color(256, 256, &Color::black()).unwrap();
+    let shader = MaterialShader {
+        model: &model,
+        material: Material::new(&diffuse)
+            .with_specular(&specular)
+            .with_glow(&glow),
+        mvp: camera.view_projection_matrix(),
+        eye: camera.eye,
+        lights: &lights,
+    };
+    draw_triangles(&mut framebuffer, &shader, model.verts.len() / 3);
+
+    let reference = Texture2D::load("tests/reference/african_head_regression.png").unwrap();
+    assert_eq!((reference.width, reference.height), (256, 256));
+    for y in 0..256 {
+        for x in 0..256 {
+            let color = framebuffer.get_color(x, y).unwrap();
+            let expected = reference.get_color(x, y).unwrap();
+            assert!(
+                color.r.abs_diff(expected.r) <= 2
+                    && color.g.abs_diff(expected.g) <= 2
+                    && color.b.abs_diff(expected.b) <= 2,
+                "({x}, {y}): {color:?} != {expected:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_srgb() {
+    let dir = std::env::temp_dir();
+    let path = dir.join("tinyrenderer_srgb.png");
+    Texture2D::create_init_color(2, 2, &Color::new(188, 188, 188, 255))
+        .unwrap()
+        .write(&path)
+        .unwrap();
+    let linear = Texture2D::load(&path).unwrap();
+    let srgb = Texture2D::load_srgb(&path).unwrap();
+    assert_eq!(linear.color_space(), ColorSpace::Linear);
+    assert_eq!(srgb.color_space(), ColorSpace::Srgb);
+    let sample = |texture: &Texture2D| {
+        texture.texture(
+            0.5f32,
+            0.5f32,
+            Texture2DWrapMode::ClampToEdge,
+            Texture2DFilterMode::Linear,
+        )
+    };
+    assert_relative_eq!(sample(&linear).x, 188f32 / 255f32);
+    assert_relative_eq!(sample(&srgb).x, 0.50289f32, epsilon = 1e-5);
+
+    // Half of the samples of the pixel are covered by a white triangle, the average is taken on
+    // linear values for an sRGB framebuffer.
+    let shader = CountingShader {
+        verts: vec![
+            Vec4::new(-5f32, -3f32, 0f32, 1f32),
+            Vec4::new(0f32, -3f32, 0f32, 1f32),
+            Vec4::new(0f32, 5f32, 0f32, 1f32),
+        ],
+        color: Colorf::new(1f32, 1f32, 1f32, 1f32),
+        fragments: AtomicUsize::new(0),
+    };
+    for (color_space, expected) in [(ColorSpace::Linear, 128), (ColorSpace::Srgb, 188)] {
+        let mut framebuffer =
+            Framebuffer::create_multisample_init_color(1, 1, 4, &Color::black()).unwrap();
+        framebuffer.set_color_space(color_space);
+        draw_triangles(&mut framebuffer, &shader, 1);
+        assert_eq!(framebuffer.resolve().get_color(0, 0).unwrap().r, expected);
+
+        let mut framebuffer = Framebuffer::create_init_color(1, 1, &Color::black()).unwrap();
+        framebuffer.set_color_space(color_space);
+        framebuffer.set_blend_state(&BlendState::alpha());
+        framebuffer.set_color_with_depth(0, 0, 0f32, &Color::new(255, 255, 255, 128));
+        let color = framebuffer.get_color(0, 0).unwrap();
+        assert!(color.r.abs_diff(expected) <= 1, "{:?}", color);
+        framebuffer
+            .clear_attachment_with(0, &Colorf::new(0.5f32, 0.5f32, 0.5f32, 1f32))
+            .unwrap();
+        assert_eq!(framebuffer.get_color(0, 0).unwrap().r, expected);
+    }
+}
+
+#[test]
+fn test_hdr_tone_mapping() {
+    let shader = CountingShader {
+        verts: quad_shader().verts.to_vec(),
+        color: Colorf::new(4f32, 2f32, 0.5f32, 1f32),
+        fragments: AtomicUsize::new(0),
+    };
+    let mut framebuffer = Framebuffer::create_multisample_hdr(4, 4, 4).unwrap();
+    assert!(framebuffer.is_hdr());
+    assert_eq!(
+        framebuffer.attachment_format(0),
+        Some(AttachmentFormat::Rgba32F)
+    );
+    framebuffer.set_depth_test(false);
+    framebuffer.set_blend_state(&BlendState::additive());
+    draw_triangles(&mut framebuffer, &shader, 2);
+    draw_triangles(&mut framebuffer, &shader, 2);
+    // Colors are accumulated without clamping.
+    let expected = Colorf::new(8f32, 4f32, 1f32, 2f32);
+    assert_relative_eq!(framebuffer.get_attachment(0, 1, 1).unwrap(), expected);
+    // There is no 8-bit color buffer to read back.
+    assert!(framebuffer.get_color(1, 1).is_err());
+    assert!(framebuffer.to_u32_slice().is_err());
+    let resolved = framebuffer.resolve();
+    assert!(resolved.is_hdr());
+    assert_relative_eq!(resolved.get_attachment(0, 1, 1).unwrap(), expected);
+
+    let mut target = Framebuffer::create(4, 4).unwrap();
+    let tone_mapping = ToneMapping::new(ToneMapOperator::Reinhard);
+    framebuffer.tone_map_to(&mut target, &tone_mapping).unwrap();
+    assert_eq!(
+        *target.get_color(1, 1).unwrap(),
+        Color::new(227, 204, 128, 255)
+    );
+    framebuffer
+        .tone_map_to(&mut target, &tone_mapping.with_exposure(-1f32))
+        .unwrap();
+    assert_eq!(
+        *target.get_color(1, 1).unwrap(),
+        Color::new(204, 170, 85, 255)
+    );
+    let tone_mapping = ToneMapping::new(ToneMapOperator::Clamp);
+    framebuffer.tone_map_to(&mut target, &tone_mapping).unwrap();
+    assert_eq!(*target.get_color(1, 1).unwrap(), Color::white());
+    assert!(framebuffer
+        .tone_map_to(&mut Framebuffer::create_hdr(4, 4).unwrap(), &tone_mapping)
+        .is_err());
+    assert!(framebuffer
+        .resolve_to(&mut Framebuffer::create(4, 4).unwrap())
+        .is_err());
+    let path = std::env::temp_dir().join("tinyrenderer_hdr.png");
+    framebuffer.write(&path).unwrap();
+    assert!(path.exists());
+}
+
+// Replaces every color with `scale * color + offset`, and writes the depth to alpha.
+struct AffinePass {
+    scale: f32,
+    offset: f32,
+}
+
+impl PostProcessPass for AffinePass {
+    fn apply(
+        &self,
+        source: &Framebuffer,
+        target: &mut Framebuffer,
+    ) -> Result<(), FramebufferError> {
+        for y in 0..source.height {
+            for x in 0..source.width {
+                let color =
+                    source.get_attachment(0, x, y)?.xyz() * self.scale + Vec3::repeat(self.offset);
+                target.set_attachment(0, x, y, &color.push(source.get_depth(x, y)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_post_process() {
+    let mut framebuffer = Framebuffer::create_hdr(32, 32).unwrap();
+    framebuffer.clear_depth_with(0.25f32);
+    let mut post_process = PostProcess::new();
+    post_process.add_pass(AffinePass {
+        scale: 1f32,
+        offset: 1f32,
+    });
+    post_process.add_pass(AffinePass {
+        scale: 3f32,
+        offset: 0f32,
+    });
+    assert_eq!(post_process.pass_count(), 2);
+    post_process.apply(&mut framebuffer).unwrap();
+    // Passes run in order and the depth buffer stays available.
+    assert_relative_eq!(
+        framebuffer.get_attachment(0, 5, 5).unwrap(),
+        Colorf::new(3f32, 3f32, 3f32, 0.25f32)
+    );
+    assert!(post_process
+        .apply(&mut Framebuffer::create_multisample_hdr(32, 32, 4).unwrap())
+        .is_err());
+
+    // A single pixel above the threshold spreads to its surroundings.
+    let mut post_process = PostProcess::new();
+    post_process.add_pass(Bloom::new(1f32, 1f32));
+    framebuffer
+        .clear_attachment_with(0, &Colorf::new(0.5f32, 0.5f32, 0.5f32, 1f32))
+        .unwrap();
+    framebuffer
+        .set_attachment(0, 16, 16, &Colorf::new(64f32, 32f32, 0.5f32, 1f32))
+        .unwrap();
+    post_process.apply(&mut framebuffer).unwrap();
+    let center = framebuffer.get_attachment(0, 16, 16).unwrap();
+    assert!(center.x > 64f32 && center.y > 32f32);
+    let near = framebuffer.get_attachment(0, 18, 17).unwrap();
+    assert!(near.x > near.y && near.y > 0.5f32);
+    assert!(near.z < near.y);
+    let far = framebuffer.get_attachment(0, 0, 31).unwrap();
+    assert!(far.x - 0.5f32 < near.x - 0.5f32);
+    assert_relative_eq!(framebuffer.get_attachment(0, 0, 0).unwrap().w, 1f32);
+}
+
+// Pixels whose luma differs from the one of a horizontal or vertical neighbor by more than 7/8 of
+// the range, the steps of aliased edges.
+fn hard_edge_count(framebuffer: &Framebuffer) -> usize {
+    let luma = |x: i32, y: i32| {
+        let color = framebuffer.get_color(x, y).unwrap();
+        (color.r as i32 * 299 + color.g as i32 * 587 + color.b as i32 * 114) / 1000
+    };
+    let mut count = 0;
+    for y in 1..framebuffer.height - 1 {
+        for x in 1..framebuffer.width - 1 {
+            let center = luma(x, y);
+            if [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .any(|(dx, dy)| (luma(x + dx, y + dy) - center).abs() > 224)
+            {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+#[test]
+fn test_fxaa() {
+    let model = Model::load("assets/african_head/african_head.obj").unwrap();
+    let texture = Texture2D::create_init_color(1, 1, &Color::white()).unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_4, 1f32, 0.1f32, 100f32);
+    camera.look_at(&Vec3::new(1f32, 0.5f32, 3f32), &Vec3::zeros(), &Vec3::y());
+    let shader = TextureShader {
+        model: &model,
+        texture: &texture,
+        mvp: camera.view_projection_matrix(),
+    };
+    let mut framebuffer = Framebuffer::create_init_color(128, 128, &Color::black()).unwrap();
+    draw_triangles(&mut framebuffer, &shader, model.verts.len() / 3);
+    let before = hard_edge_count(&framebuffer);
+    let mut post_process = PostProcess::new();
+    post_process.add_pass(Fxaa::default());
+    post_process.apply(&mut framebuffer).unwrap();
+    let after = hard_edge_count(&framebuffer);
+    // The silhouette of the head is smoothed, flat areas are left unchanged.
+    assert!(before > 200);
+    assert!(after * 4 < before);
+    assert_eq!(*framebuffer.get_color(0, 0).unwrap(), Color::black());
+    assert_eq!(*framebuffer.get_color(64, 64).unwrap(), Color::white());
+}
+
+#[test]
+fn test_ssao() {
+    let model = Model::load("assets/african_head/african_head.obj").unwrap();
+    let texture = Texture2D::create_init_color(1, 1, &Color::white()).unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_4, 1f32, 0.1f32, 100f32);
+    camera.look_at(&Vec3::new(1f32, 0.5f32, 3f32), &Vec3::zeros(), &Vec3::y());
+    let mut gbuffer = GBuffer::create(128, 128).unwrap();
+    gbuffer.clear();
+    let shader = GBufferShader {
+        model: &model,
+        diffuse: &texture,
+        specular: None,
+        specular_intensity: 0f32,
+        mvp: camera.view_projection_matrix(),
+    };
+    draw_triangles(gbuffer.framebuffer_mut(), &shader, model.verts.len() / 3);
+
+    let mut ssao = Ssao::new(16);
+    ssao.compute(gbuffer.framebuffer(), &camera, Some(GBUFFER_NORMAL))
+        .unwrap();
+    let mut reconstructed = Ssao::new(16);
+    reconstructed
+        .compute(gbuffer.framebuffer(), &camera, None)
+        .unwrap();
+    let mut occluded = 0;
+    let mut difference = 0f32;
+    let mut count = 0;
+    for y in 0..128 {
+        for x in 0..128 {
+            let visibility = ssao.get(x, y);
+            assert!((0f32..=1f32).contains(&visibility));
+            if gbuffer.framebuffer().get_depth(x, y) == f32::MIN {
+                assert_eq!(visibility, 1f32);
+                assert_eq!(reconstructed.get(x, y), 1f32);
+            } else {
+                if visibility < 0.9f32 {
+                    occluded += 1;
+                }
+                difference += (visibility - reconstructed.get(x, y)).abs();
+                count += 1;
+            }
+        }
+    }
+    // Creases of the head are occluded, and normals reconstructed from the depth buffer give
+    // about the same result as the normals of the G-buffer.
+    assert!(occluded > 30);
+    assert!(difference / (count as f32) < 0.05f32);
+
+    // Results are reproducible.
+    let mut again = Ssao::new(16);
+    again
+        .compute(gbuffer.framebuffer(), &camera, Some(GBUFFER_NORMAL))
+        .unwrap();
+    assert_eq!(again.get(64, 64), ssao.get(64, 64));
+    let position = Vec3::new(0f32, 0f32, 0.5f32);
+    assert!((0f32..=1f32).contains(&ssao.visibility(&position)));
+
+    // An empty kernel occludes nothing, and a degenerate projection is an error.
+    let mut empty = Ssao::new(0);
+    empty
+        .compute(gbuffer.framebuffer(), &camera, Some(GBUFFER_NORMAL))
+        .unwrap();
+    assert_eq!(empty.get(64, 64), 1f32);
+    let degenerate = Camera::orthographic(0f32, 0f32, 0f32, 0f32, 1f32, 3f32);
+    assert!(ssao
+        .compute(gbuffer.framebuffer(), &degenerate, None)
+        .is_err());
+
+    // With a reversed depth range, a Less compare and a depth buffer cleared to 1, the background
+    // is still found uncovered.
+    gbuffer.clear();
+    let framebuffer = gbuffer.framebuffer_mut();
+    let viewport = framebuffer.viewport().with_depth_range(1f32, 0f32);
+    framebuffer.set_viewport(&viewport);
+    framebuffer.set_depth_state(&DepthState {
+        compare: CompareFunction::Less,
+        ..Default::default()
+    });
+    framebuffer.clear_depth_with(1f32);
+    draw_triangles(framebuffer, &shader, model.verts.len() / 3);
+    reconstructed.clear_depth = 1f32;
+    for normal_attachment in [Some(GBUFFER_NORMAL), None] {
+        let ssao = if normal_attachment.is_some() {
+            &mut ssao
+        } else {
+            &mut reconstructed
+        };
+        ssao.compute(gbuffer.framebuffer(), &camera, normal_attachment)
+            .unwrap();
+        let mut occluded = 0;
+        for y in 0..128 {
+            for x in 0..128 {
+                let normal = gbuffer
+                    .framebuffer()
+                    .get_attachment(GBUFFER_NORMAL, x, y)
+                    .unwrap();
+                if normal.w == 0f32 {
+                    assert_eq!(ssao.get(x, y), 1f32);
+                } else if ssao.get(x, y) < 0.9f32 {
+                    occluded += 1;
+                }
+            }
+        }
+        assert!(occluded > 30);
+    }
+}
+
+struct PbrShader<'a> {
+    model: &'a Model,
+    material: PbrMaterial<'a>,
+    mvp: Mat4,
+    eye: Vec3,
+    lights: &'a [Light],
+}
+
+impl Shader for PbrShader<'_> {
+    type Varying = (Vec2, Vec3, Vec3);
+
+    fn vertex(&self, face: usize, vert: usize) -> (Vec4, Self::Varying) {
+        let i = face * 3 + vert;
+        let pos = self.model.verts[i];
+        (
+            self.mvp * pos.push(1f32),
+            (self.model.uvs[i], self.model.norms[i], pos),
+        )
+    }
+
+    fn fragment(&self, varying: &Self::Varying) -> Option<Colorf> {
+        let (uv, norm, pos) = varying;
+        Some(self.material.shade(
+            uv,
+            pos,
+            &norm.normalize(),
+            &self.eye,
+            self.lights,
+            1f32,
+            |_| 1f32,
+        ))
+    }
+}
+
+#[test]
+fn test_pbr() {
+    let model = Model::load("assets/african_head/african_head.obj").unwrap();
+    let texture = Texture2D::load_srgb("assets/african_head/african_head_diffuse.png").unwrap();
+    let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_4, 1f32, 0.1f32, 100f32);
+    camera.look_at(&Vec3::new(1f32, 0.5f32, 3f32), &Vec3::zeros(), &Vec3::y());
+    let lights = [Light::directional(
+        Vec3::new(-1f32, -1f32, -2f32),
+        Vec3::repeat(1f32),
+        1f32,
+    )];
+    let render = |shader: &dyn Fn(&mut Framebuffer)| {
+        let mut framebuffer = Framebuffer::create_hdr(128, 128).unwrap();
+        shader(&mut framebuffer);
+        let mut colors = Vec::new();
+        for y in 0..128 {
+            for x in 0..128 {
+                colors.push(framebuffer.get_attachment(0, x, y).unwrap().xyz());
+            }
+        }
+        colors
+    };
+    let lambert = render(&|framebuffer| {
+        let shader = MaterialShader {
+            model: &model,
+            material: Material::new(&texture),
+            mvp: camera.view_projection_matrix(),
+            eye: camera.eye,
+            lights: &lights,
+        };
+        draw_triangles(framebuffer, &shader, model.verts.len() / 3);
+    });
+    let pbr = |metallic: f32, roughness: f32| {
+        render(&|framebuffer| {
+            let shader = PbrShader {
+                model: &model,
+                material: PbrMaterial::new(&Colorf::repeat(1f32), metallic, roughness)
+                    .with_base_color(&texture),
+                mvp: camera.view_projection_matrix(),
+                eye: camera.eye,
+                lights: &lights,
+            };
+            draw_triangles(framebuffer, &shader, model.verts.len() / 3);
+        })
+    };
+
+    // A rough dielectric is close to the Lambert term.
+    let dielectric = pbr(0f32, 1f32);
+    let difference: f32 = lambert
+        .iter()
+        .zip(&dielectric)
+        .map(|(a, b)| (a - b).abs().max())
+        .sum::<f32>()
+        / lambert.len() as f32;
+    assert!(difference < 0.01f32);
+    // A smooth metal has no diffuse light, and a brighter highlight.
+    let metal = pbr(1f32, 0.3f32);
+    let total = |colors: &[Vec3]| colors.iter().sum::<Vec3>();
+    let brightest = |colors: &[Vec3]| colors.iter().map(|color| color.max()).fold(0f32, f32::max);
+    assert!(brightest(&metal) > brightest(&dielectric));
+    assert!(total(&metal).max() < total(&dielectric).max());
+}